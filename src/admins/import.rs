@@ -1,11 +1,11 @@
 use sqlx::{Pool, Postgres};
 use crate::AppError;
-use std::path::PathBuf;
-use std::io::BufReader;
-use std::fs::File;
-use csv::ReaderBuilder;
-use super::data_vectors::AdminVecs;
+use std::path::{Path, PathBuf};
+use crate::setup::source_file::{file_name_of, SourceFile};
+use crate::setup::bulk_writer::{BulkWriter, CopyRow, RowWriter};
 use log::info;
+use crate::setup::run_history::record_count;
+use crate::normalise::NameRules;
 
 #[derive(serde::Deserialize)]
 #[allow(dead_code)]
@@ -21,23 +21,24 @@ pub struct AdminRec {
     pub id: i64,
     pub code: String,
     pub name: String,
+    pub orig_name: String,
+}
+
+impl CopyRow for AdminRec {
+    fn write_row(&self, row: &mut RowWriter) {
+        row.add(&self.id).add(&self.code).add(&self.name).add(&self.orig_name);
+    }
 }
 
 
-pub async fn import_admins_data(data_folder: &PathBuf, source_file_name: &str, pool: &Pool<Postgres>) -> Result<(), AppError> {
+pub async fn import_admins_data(source_file: &PathBuf, table_name: &str, rules: &NameRules, pool: &Pool<Postgres>) -> Result<(), AppError> {
 
-    let source_file_path: PathBuf = [data_folder, &PathBuf::from(source_file_name)].iter().collect();
-    let file = File::open(&source_file_path)
-                .map_err(|e| AppError::IoWriteErrorWithPath(e, source_file_path))?;
-    let buf_reader = BufReader::new(file);
-    let mut csv_rdr = ReaderBuilder::new()
-        .has_headers(false)
-        .delimiter(9)
-        .from_reader(buf_reader);
+    let source_file_name = &file_name_of(source_file);
+    let mut source = SourceFile::open_source(source_file)?;
+    let mut csv_rdr = source.csv_reader(Some("code"), false)?;
 
         let mut i = 0;
-        let vector_size = 5000;
-        let mut dv: AdminVecs = AdminVecs::new(vector_size);
+        let mut bw = BulkWriter::new(pool, &format!("geo.{}", table_name), &["id", "code", "name", "orig_name"]);
              
         for result in csv_rdr.deserialize() {
      
@@ -46,21 +47,32 @@ pub async fn import_admins_data(data_folder: &PathBuf, source_file_name: &str, p
             let admin_rec = AdminRec {
                 id: source.geonameid,
                 code: source.code,
-                name: source.name.trim().replace(".", "").replace("'", "’"),
+                name: rules.normalise(&source.name),
+                orig_name: source.name,
             };
 
-            dv.add_data(&admin_rec);   // transfer data to vectors
+            bw.add(&admin_rec).await?;
             i +=1;    
 
          }
 
-         let mut table_name = "adm2s";
-         if source_file_name.to_string().starts_with("admin1") {
-            table_name = "adm1s";
-         }
-              
-         dv.store_data(&pool, table_name).await?;
+         csv_rdr.get_ref().log_skipped_lines(source_file_name);
+         bw.finish().await?;
          info!("{} records processed from {} to geo.{}", i, source_file_name, table_name);
+         record_count(&format!("{} source records", table_name), i);
                  
          Ok(())
-}
\ No newline at end of file
+}
+
+pub fn read_admin_ids(source_file: &Path) -> Result<Vec<i64>, AppError> {
+
+    let mut source = SourceFile::open_source(source_file)?;
+    let mut csv_rdr = source.csv_reader(Some("code"), false)?;
+
+    let mut ids = Vec::new();
+    for result in csv_rdr.deserialize() {
+        let source: AdminLine = result?;
+        ids.push(source.geonameid);
+    }
+    Ok(ids)
+}
@@ -1,9 +1,11 @@
-mod data_vectors;
 mod import;
 
 use sqlx::{Pool, Postgres};
 use crate::AppError;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use log::info;
+use crate::setup::run_history::record_count;
+use crate::normalise::{add_search_keys, NameRules};
 
 pub async fn create_admins_tables(pool: &Pool<Postgres>) -> Result<(), AppError> {
 
@@ -13,6 +15,7 @@ pub async fn create_admins_tables(pool: &Pool<Postgres>) -> Result<(), AppError>
                     id              int
                   , code            varchar   
                   , name            varchar  
+                  , orig_name       varchar
                 );"#;
    
     sqlx::raw_sql(sql).execute(pool)
@@ -24,17 +27,222 @@ pub async fn create_admins_tables(pool: &Pool<Postgres>) -> Result<(), AppError>
                     id              int
                   , code            varchar   
                   , name            varchar  
+                  , orig_name       varchar
                 );"#;
 
     sqlx::raw_sql(sql).execute(pool)
         .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
 
+    let sql = r#"drop table if exists src_next.admin1s;
+                create table src_next.admin1s
+                (
+                    id                    int primary key
+                  , code                  varchar
+                  , name                  varchar
+                  , orig_name             varchar
+                  , country_id            int
+                  , country_code          varchar
+                  , country_name          varchar
+                );
+            create index admin1s_code on src_next.admin1s(code);
+
+            drop table if exists src_next.admin2s;
+                create table src_next.admin2s
+                (
+                    id                    int primary key
+                  , code                  varchar
+                  , name                  varchar
+                  , orig_name             varchar
+                  , admin1_id             int
+                  , admin1_code           varchar
+                  , admin1_name           varchar
+                  , country_id            int
+                  , country_code          varchar
+                  , country_name          varchar
+                );
+            create index admin2s_code on src_next.admin2s(code);
+
+            drop table if exists src_next.admin_names;
+            create table src_next.admin_names
+            (
+                  id                    int PRIMARY KEY GENERATED ALWAYS AS IDENTITY (START WITH 100001 INCREMENT BY 1)
+                , admin_id              int
+                , admin_level           int
+                , admin_name            varchar
+                , country_id            int
+                , country_name          varchar
+                , alt_name              varchar
+                , orig_alt_name         varchar
+                , search_key            varchar
+                , langlist              varchar
+                , lang_codes            text[] default '{}'
+                , is_preferred          bool default false
+                , is_short              bool default false
+                , is_colloquial         bool default false
+                , is_historic           bool default false
+                , is_transliterated     bool default false
+                , yfrom                 int
+                , yto                   int
+            );
+            create index admin_names_admin_id on src_next.admin_names(admin_id);
+            create index admin_names_alt_name on src_next.admin_names(alt_name);
+            create index admin_names_lang_codes on src_next.admin_names using gin(lang_codes);
+            create index admin_names_search_key on src_next.admin_names(search_key);"#;
+
+    sqlx::raw_sql(sql).execute(pool)
+        .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    Ok(())
+}
+
+
+pub async fn import_data(source_file: &PathBuf, table_name: &str, rules: &NameRules, pool: &Pool<Postgres>) -> Result<(), AppError> {
+
+    import::import_admins_data(source_file, table_name, rules, pool).await
+
+}
+
+
+pub fn read_ids(source_file: &Path) -> Result<Vec<i64>, AppError> {
+
+    import::read_admin_ids(source_file)
+}
+
+
+pub async fn transfer_data(pool: &Pool<Postgres>) -> Result<(), AppError> {
+
+    // Called once both the adm1s and adm2s tables have been imported.
+
+    transfer_admins(pool).await?;
+    update_orphaned_admins(pool).await?;
+    create_admin_names(pool).await?;
+    add_missing_admin_names(pool).await?;
+    add_search_keys("admin_names", "alt_name", pool).await?;
+
+    Ok(())
+}
+
+
+async fn transfer_admins(pool: &Pool<Postgres>) -> Result<(), AppError> {
+
+    // Admin codes are prefixed by the country's iso code (e.g. DE.02, DE.02.091),
+    // which is used to link each area to its country, and, for admin2 areas,
+    // the first two parts of the code to link to the parent admin1 area.
+
+    let sql = r#"insert into src_next.admin1s (id, code, name, orig_name,
+              country_id, country_code, country_name)
+              select a.id, a.code, a.name, a.orig_name,
+              c.id, split_part(a.code, '.', 1), c.country_name
+              from geo.adm1s a
+              left join src_next.countries c
+              on split_part(a.code, '.', 1) = c.iso_code
+              order by a.code;"#;
+
+    let res = sqlx::raw_sql(sql).execute(pool)
+                .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    info!("{} admin1 records transferred to src schema", res.rows_affected());
+    record_count("admin1s transferred", res.rows_affected());
+
+    let sql = r#"insert into src_next.admin2s (id, code, name, orig_name,
+              admin1_id, admin1_code, admin1_name,
+              country_id, country_code, country_name)
+              select a.id, a.code, a.name, a.orig_name,
+              p.id, split_part(a.code, '.', 1)||'.'||split_part(a.code, '.', 2), p.name,
+              c.id, split_part(a.code, '.', 1), c.country_name
+              from geo.adm2s a
+              left join geo.adm1s p
+              on split_part(a.code, '.', 1)||'.'||split_part(a.code, '.', 2) = p.code
+              left join src_next.countries c
+              on split_part(a.code, '.', 1) = c.iso_code
+              order by a.code;"#;
+
+    let res = sqlx::raw_sql(sql).execute(pool)
+                .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    info!("{} admin2 records transferred to src schema", res.rows_affected());
+    record_count("admin2s transferred", res.rows_affected());
+
+    Ok(())
+}
+
+
+async fn update_orphaned_admins(pool: &Pool<Postgres>) -> Result<(), AppError> {
+
+    // As for cities, the admin areas of Hong Kong and Macao are re-assigned 
+    // to China (but retain their original codes).
+
+    let sql = r#"update src_next.admin1s
+                set country_id = 1814991,
+                country_code = 'CN',
+                country_name = 'China'
+                where country_code in ('HK', 'MO');
+
+                update src_next.admin2s
+                set country_id = 1814991,
+                country_code = 'CN',
+                country_name = 'China'
+                where country_code in ('HK', 'MO');"#;
+
+    sqlx::raw_sql(sql).execute(pool)
+        .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
     Ok(())
 }
 
 
-pub async fn import_data(data_folder: &PathBuf, source_file_name: &str, pool: &Pool<Postgres>) -> Result<(), AppError> {
+async fn create_admin_names(pool: &Pool<Postgres>) -> Result<(), AppError> {
 
-    import::import_admins_data(data_folder, source_file_name, pool).await
+    let sql = r#"insert into src_next.admin_names (admin_id, admin_level, admin_name, 
+         country_id, country_name, alt_name, orig_alt_name, langlist, lang_codes,
+         is_preferred, is_short, is_colloquial, is_historic, is_transliterated, yfrom, yto)
+         select a.id, 1, a.name, a.country_id, a.country_name, n.alt_name, n.orig_name, n.langs, n.lang_codes,
+         n.is_preferred, n.is_short, n.is_colloquial, n.is_historic, n.is_transliterated, n.yfrom, n.yto
+         from src_next.admin1s a 
+         inner join geo.alt_names n
+         on a.id = n.id;
+         
+         insert into src_next.admin_names (admin_id, admin_level, admin_name, 
+         country_id, country_name, alt_name, orig_alt_name, langlist, lang_codes,
+         is_preferred, is_short, is_colloquial, is_historic, is_transliterated, yfrom, yto)
+         select a.id, 2, a.name, a.country_id, a.country_name, n.alt_name, n.orig_name, n.langs, n.lang_codes,
+         n.is_preferred, n.is_short, n.is_colloquial, n.is_historic, n.is_transliterated, n.yfrom, n.yto
+         from src_next.admin2s a 
+         inner join geo.alt_names n
+         on a.id = n.id;"#;
 
-}
\ No newline at end of file
+    let res = sqlx::raw_sql(sql).execute(pool)
+        .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    info!("{} admin name records created", res.rows_affected());
+    record_count("admin names created", res.rows_affected());
+
+    Ok(())
+}
+
+
+async fn add_missing_admin_names(pool: &Pool<Postgres>) -> Result<(), AppError> {
+
+    // As with cities, not every admin area has an alt_name matching its 
+    // geonames name - this ensures that all names are in the admin_names table.
+
+    let sql = r#"insert into src_next.admin_names (admin_id, admin_level, admin_name, 
+         country_id, country_name, alt_name, orig_alt_name, langlist)
+         select a.id, a.level, a.name, a.country_id, a.country_name, a.name, a.orig_name, ''
+         from 
+            (select id, 1 as level, name, orig_name, country_id, country_name from src_next.admin1s
+             union all
+             select id, 2 as level, name, orig_name, country_id, country_name from src_next.admin2s) a
+         left join src_next.admin_names n
+         on a.id = n.admin_id
+         and a.name = n.alt_name
+         where n.id is null;"#;
+
+    let res = sqlx::raw_sql(sql).execute(pool)
+        .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    info!("{} missing admin default names added to the admin_names table", res.rows_affected());
+    record_count("missing admin names added", res.rows_affected());
+
+    Ok(())
+}
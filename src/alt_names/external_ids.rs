@@ -0,0 +1,96 @@
+/***************************************************************************
+ * Identifiers that link geonames entities to other knowledge bases. The
+ * alternate names file lists Wikidata QIDs (language 'wkdt') and links,
+ * mostly to Wikipedia articles (language 'link'). For Wikipedia links the
+ * language of the article is taken from the host name (en.wikipedia.org).
+ ***************************************************************************/
+
+use crate::setup::bulk_writer::{CopyRow, RowWriter};
+
+pub const EXTERNAL_ID_TYPES: [&str; 2] = ["wkdt", "link"];
+
+#[derive(Debug)]
+pub struct ExternalId {
+    pub geo_id: i64,
+    pub id_type: &'static str,
+    pub lang: Option<String>,
+    pub ext_id: String,
+}
+
+impl CopyRow for ExternalId {
+    fn write_row(&self, row: &mut RowWriter) {
+        row.add(&self.geo_id).add(&self.id_type).add(&self.lang).add(&self.ext_id);
+    }
+}
+
+
+impl ExternalId {
+
+    pub fn new(geo_id: i64, code_type: &str, value: &str) -> ExternalId {
+
+        let value = value.trim();
+
+        // Links are classed as wikipedia or as other links.
+
+        let (id_type, lang) = match code_type {
+            "wkdt" => ("wikidata", None),
+            _ => match wikipedia_lang(value) {
+                Some(l) => ("wikipedia", Some(l)),
+                None => ("link", None),
+            },
+        };
+
+        ExternalId {
+            geo_id,
+            id_type,
+            lang,
+            ext_id: value.to_string(),
+        }
+    }
+}
+
+
+pub fn wikipedia_lang(url: &str) -> Option<String> {
+
+    // Returns the language subdomain of a wikipedia url, or None if the
+    // url is not a wikipedia one. Mobile links (en.m.wikipedia.org) are included.
+
+    let host = url.split("://").nth(1)?.split('/').next()?.to_lowercase();
+    let lang = host.strip_suffix(".wikipedia.org")?;
+    let lang = lang.strip_suffix(".m").unwrap_or(lang);
+
+    match !lang.is_empty() && lang != "www" && !lang.contains('.') {
+        true => Some(lang.to_string()),
+        false => None,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_wikipedia_langs_found() {
+        assert_eq!(wikipedia_lang("https://en.wikipedia.org/wiki/London"), Some("en".to_string()));
+        assert_eq!(wikipedia_lang("http://fr.wikipedia.org/wiki/Royaume-Uni"), Some("fr".to_string()));
+        assert_eq!(wikipedia_lang("https://zh-yue.m.wikipedia.org/wiki/X"), Some("zh-yue".to_string()));
+    }
+
+    #[test]
+    fn check_other_links_not_wikipedia() {
+        assert_eq!(wikipedia_lang("https://www.wikipedia.org/"), None);
+        assert_eq!(wikipedia_lang("https://www.london.gov.uk/"), None);
+        assert_eq!(wikipedia_lang("en.wikipedia.org/wiki/London"), None);
+    }
+
+    #[test]
+    fn check_external_ids_classified() {
+        let w = ExternalId::new(2643743, "wkdt", "Q84");
+        assert_eq!((w.id_type, w.lang), ("wikidata", None));
+        let l = ExternalId::new(2643743, "link", "https://de.wikipedia.org/wiki/London");
+        assert_eq!((l.id_type, l.lang.as_deref()), ("wikipedia", Some("de")));
+        let o = ExternalId::new(2643743, "link", "https://www.london.gov.uk/");
+        assert_eq!(o.id_type, "link");
+    }
+}
@@ -1,11 +1,24 @@
 use sqlx::{postgres::PgQueryResult, Pool, Postgres};
 use crate::AppError;
-use std::path::PathBuf;
-use std::io::BufReader;
-use std::fs::File;
-use csv::ReaderBuilder;
-use super::data_vectors::AltRecVecs;
-use log::info;
+use std::path::Path;
+use crate::setup::source_file::{file_name_of, SourceFile};
+use crate::setup::bulk_writer::{write_batch, BatchBuilder, CopyBatch, CopyRow, RowWriter};
+use super::external_ids::{ExternalId, EXTERNAL_ID_TYPES};
+use log::{info, warn};
+use crate::setup::run_history::record_count;
+use super::scripts::excluded_script;
+use super::lang_tags::parse_lang_tag;
+use super::transliterate::latin_alias;
+use std::collections::{HashMap, HashSet};
+use unicode_script::Script;
+use std::time::Instant;
+use std::future::Future;
+use crate::normalise::NameRules;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
 
 
 #[derive(serde::Deserialize)]
@@ -27,94 +40,304 @@ struct AltName {
 pub struct AltRec {
     pub geo_id: i64,
     pub name: String,
+    pub orig_name: String,
     pub lang: String,
+    pub lang_code: Option<String>,
+    pub region: Option<String>,
+    pub is_preferred: bool,
+    pub is_short: bool,
+    pub is_colloquial: bool,
+    pub is_historic: bool,
+    pub is_transliterated: bool,
+    pub yfrom: Option<i32>,
+    pub yto: Option<i32>,
 }
 
+impl CopyRow for AltRec {
+    fn write_row(&self, row: &mut RowWriter) {
+        row.add(&self.geo_id).add(&self.name).add(&self.orig_name).add(&self.lang).add(&self.lang_code).add(&self.region)
+           .add(&self.is_preferred).add(&self.is_short).add(&self.is_colloquial).add(&self.is_historic)
+           .add(&self.is_transliterated).add(&self.yfrom).add(&self.yto);
+    }
+}
+
+#[derive(Debug)]
+pub struct PlaceCode {
+    pub geo_id: i64,
+    pub code_type: String,
+    pub code: String,
+}
+
+impl CopyRow for PlaceCode {
+    fn write_row(&self, row: &mut RowWriter) {
+        row.add(&self.geo_id).add(&self.code_type).add(&self.code);
+    }
+}
+
+// Pseudo language codes used for codes rather than names. These go to
+// src.place_codes, while links and wikidata ids go to src.external_ids.
+
+const PLACE_CODE_TYPES: [&str; 6] = ["iata", "icao", "unlc", "faac", "post", "abbr"];
+
+
+pub async fn import_alt_name_data(source_file: &Path, pool: &Pool<Postgres>, scripts: Option<&[Script]>, 
+                                   transliterate: bool, rules: &NameRules, kept_ids: HashSet<i64>, 
+                                   writer_tasks: usize) -> Result<(), AppError> {
+
+    // All selected records are collected before any are aggregated, so that
+    // the result does not depend on the file being sorted by geoname id.
+
+    create_collecting_table(pool).await?;
+    let started = Instant::now();
+
+    // The file is parsed on a separate thread, which passes batches of rows, 
+    // ready for COPY, through a bounded channel to several writer tasks, each 
+    // using its own connection. Parsing therefore overlaps with writing, and 
+    // the channel bound stops the parser running far ahead of the writers.
+
+    // The first writer to fail closes the channel, so that the parser stops at 
+    // its next send, and sets the failed flag, so that the other writers stop 
+    // rather than loading the batches still queued.
+
+    let (tx, rx) = mpsc::channel::<CopyBatch>(writer_tasks * 2);
+    let rx = Arc::new(Mutex::new(rx));
+    let failed = Arc::new(AtomicBool::new(false));
+
+    let writers: Vec<JoinHandle<Result<u64, AppError>>> = (0..writer_tasks)
+        .map(|_| {
+            let rx = Arc::clone(&rx);
+            let failed = Arc::clone(&failed);
+            let pool = pool.clone();
+            tokio::spawn(run_writer(rx, failed, move |b| {
+                let pool = pool.clone();
+                async move { write_batch(&pool, &b).await }
+            }))
+        })
+        .collect();
+    drop(rx);
+
+    let source_file = source_file.to_path_buf();
+    let scripts = scripts.map(|s| s.to_vec());
+    let rules = rules.clone();
+    let parser_failed = Arc::clone(&failed);
+    let parser = tokio::task::spawn_blocking(move || 
+        parse_alt_names(&source_file, scripts.as_deref(), transliterate, &rules, &kept_ids, tx, &parser_failed));
+
+    let parsed = parser.await
+        .map_err(|e| AppError::TaskError(format!("alternate names parser failed: {}", e)))?;
+
+    // A writer error is reported in preference to the parser error it causes.
+
+    let mut written = 0;
+    for writer in writers {
+        written += writer.await
+            .map_err(|e| AppError::TaskError(format!("alternate names writer failed: {}", e)))??;
+    }
+    let stats = parsed?;
+
+    info!("Processed {} alternate name records in total", stats.read);
+    record_count("alt name source records", stats.read);
+    info!("{} alternate name records skipped as not for an imported place", stats.not_kept);
+    record_count("alt name records not for imported places", stats.not_kept);
+    log_load_rate(stats.read, written, writer_tasks, started);
+    log_excluded_scripts(stats.excluded);
+    if transliterate {
+        info!("{} transliterated aliases added for non-Latin alternate names", stats.transliterated);
+        record_count("alt name transliterated aliases", stats.transliterated);
+    }
+
+    // The aggregation of names and the tidying of the code tables 
+    // are independent, and so are also run concurrently.
+
+    log_unresolved_langs(pool).await?;
+    tokio::try_join!(transfer_data(pool), dedupe_place_codes(pool), dedupe_external_ids(pool))?;
+    drop_collecting_table(pool).await?;
+    verify_alt_names(pool).await?;
+            
+    Ok(())
+}
+
+
+// Years are given as whole numbers (negative for BC). Anything else is 
+// treated as unknown, so that the years can be compared numerically.
+
+fn parse_year(year: &Option<String>) -> Option<i32> {
+    year.as_deref().and_then(|y| y.trim().parse().ok())
+}
+
+
+async fn run_writer<F, Fut>(rx: Arc<Mutex<Receiver<CopyBatch>>>, failed: Arc<AtomicBool>, write: F) -> Result<u64, AppError>
+where
+    F: Fn(CopyBatch) -> Fut,
+    Fut: Future<Output = Result<u64, AppError>>,
+{
+    // Each writer takes batches from the shared channel until it is closed and 
+    // empty. write is passed in, so that stopping can be tested without a database.
+
+    let mut written = 0;
+    loop {
+        let batch = rx.lock().await.recv().await;
+        if failed.load(Ordering::Relaxed) {
+            break;
+        }
+        match batch {
+            Some(b) => match write(b).await {
+                Ok(n) => written += n,
+                Err(e) => {
+                    failed.store(true, Ordering::Relaxed);
+                    rx.lock().await.close();
+                    return Err(e);
+                },
+            },
+            None => break,   // channel closed and empty
+        }
+    }
+    Ok(written)
+}
 
-pub async fn import_alt_name_data(data_folder: &PathBuf, source_file_name: &str, pool: &Pool<Postgres>, latin_only: bool) -> Result<(), AppError> {
 
-    let source_file_path: PathBuf = [data_folder, &PathBuf::from(source_file_name)].iter().collect();
-    let file = File::open(&source_file_path)
-               .map_err(|e| AppError::IoWriteErrorWithPath(e, source_file_path))?;
-    let buf_reader = BufReader::new(file);
-    let mut csv_rdr = ReaderBuilder::new()
-        .has_headers(false)
-        .delimiter(9)
-        .from_reader(buf_reader);
+fn send_batch(tx: &Sender<CopyBatch>, failed: &AtomicBool, batch: Option<CopyBatch>) -> Result<(), AppError> {
+
+    // Sending stops, with an error, as soon as a writer has failed.
+
+    let stopped = || AppError::TaskError("alternate names writers stopped early".to_string());
+    match batch {
+        Some(_) if failed.load(Ordering::Relaxed) => Err(stopped()),
+        Some(b) => tx.blocking_send(b).map_err(|_| stopped()),
+        None => Ok(()),
+    }
+}
+
+
+struct ParseStats {
+    read: u64,
+    not_kept: u64,
+    transliterated: u64,
+    excluded: HashMap<Script, u64>,
+}
+
+
+fn parse_alt_names(source_file: &Path, scripts: Option<&[Script]>, transliterate: bool, rules: &NameRules, 
+                   kept_ids: &HashSet<i64>, tx: Sender<CopyBatch>, failed: &AtomicBool) -> Result<ParseStats, AppError> {
+
+    let source_file_name = &file_name_of(source_file);
+    let mut source = SourceFile::open_source(source_file)?;
+    let mut csv_rdr = source.csv_reader(Some("alternateNameId"), false)?;
     
    let mut i = 0;
-   let mut gid_num = 0;
-   let mut old_gid = 0;
+   let mut not_kept = 0;
+   let mut transliterated = 0;
 
    let none = "none".to_string();
-   let link = "link".to_string();
-   let wkdt = "wkdt".to_string();
-   let post = "post".to_string();
-   let unlc = "unlc".to_string();
-   let faac = "faac".to_string();
-   let icao = "icao".to_string();
-   let iata = "iata".to_string();
-   let abbr = "abbr".to_string();
-   let end_of_latin = "ZZ".to_string();
-
-   let vector_size = 5000;
-   let mut dv: AltRecVecs = AltRecVecs::new(vector_size);
-   create_collecting_table(&pool).await?;
+   let mut excluded: HashMap<Script, u64> = HashMap::new();
+
+   let mut names = BatchBuilder::new("geo.alt_src_names", &["geo_id", "alt_name", "orig_name", "lang", "lang_code", 
+        "region", "is_preferred", "is_short", "is_colloquial", "is_historic", "is_transliterated", "yfrom", "yto"]);
+   let mut codes = BatchBuilder::new("src_next.place_codes", &["geo_id", "code_type", "code"]);
+   let mut ext_ids = BatchBuilder::new("src_next.external_ids", &["geo_id", "id_type", "lang", "ext_id"]);
+
+   let send = |batch: Option<CopyBatch>| send_batch(&tx, failed, batch);
 
    for result in csv_rdr.deserialize() {
 
         let source: AltName = result?;
-        let mut create_rec = true;
-        let lang_code = source.iso_language.unwrap_or(none.clone());
+        let mut lang_code = source.iso_language.unwrap_or(none.clone());
+        let mut is_short = source.is_short_name == Some(1);
+
+        // Only names, codes and ids for the countries, admin areas, cities
+        // and regions that will be imported are retained.
+
+        let kept = kept_ids.contains(&source.geoname_id);
+        let mut create_rec = kept;
+        if !kept {
+            not_kept += 1;
+        }
         
-        if lang_code != none {
-            if lang_code == link || lang_code == wkdt
-            || lang_code == post || lang_code == unlc
-            || lang_code == faac || lang_code == icao
-            || lang_code == iata || lang_code == abbr
-            {
+        if kept && EXTERNAL_ID_TYPES.contains(&lang_code.as_str()) {
+            send(ext_ids.add(&ExternalId::new(source.geoname_id, &lang_code, &source.alternate_name)))?;
+            create_rec = false;
+        }
+
+        // Codes are stored as given. Abbreviations (e.g. 'UK', 'USA') are
+        // also kept as short alternative names, with no language.
+
+        if kept && PLACE_CODE_TYPES.contains(&lang_code.as_str()) {
+            send(codes.add(&PlaceCode {
+                geo_id: source.geoname_id,
+                code_type: lang_code.clone(),
+                code: source.alternate_name.trim().to_string(),
+            }))?;
+
+            if lang_code == "abbr" {
+                lang_code = none.clone();
+                is_short = true;
+            }
+            else {
                 create_rec = false;
             }
         }
 
-        // Optional filter here to exclude non Latin names.
-        
-        if latin_only {
-            if source.alternate_name > end_of_latin {
-                create_rec = false;
+        // Non-Latin names can also be given a Latin alias. This is done before 
+        // the script check, so that the alias is kept even if the name is not.
+        // Aliases have no language, and are never treated as preferred names.
+
+        if create_rec && transliterate {
+            if let Some(alias) = latin_alias(&source.alternate_name) {
+                send(names.add(&AltRec {
+                    geo_id: source.geoname_id,
+                    name: rules.normalise(&alias),
+                    orig_name: source.alternate_name.clone(),
+                    lang: none.clone(),
+                    lang_code: None,
+                    region: None,
+                    is_preferred: false,
+                    is_short,
+                    is_colloquial: source.is_colloquial == Some(1),
+                    is_historic: source.is_historic == Some(1),
+                    is_transliterated: true,
+                    yfrom: parse_year(&source.yfrom),
+                    yto: parse_year(&source.yto),
+                }))?;
+                transliterated += 1;
             }
         }
 
+        // Names using scripts other than those listed are excluded (unless 
+        // no list is given). Only otherwise acceptable names are counted.
+        
         if create_rec {
+            if let Some(s) = scripts {
+                if let Some(script) = excluded_script(&source.alternate_name, s) {
+                    *excluded.entry(script).or_insert(0) += 1;
+                    create_rec = false;
+                }
+            }
+        }
 
-            let geo_id = source.geoname_id;
-            if geo_id != old_gid {
-                gid_num = gid_num + 1;
-
-                if gid_num == 5000 {  // every 5000 geoname ids
+        if create_rec {
 
-                    // Call the routine to transfer records to the database.
-                    // Recreate the vectors, reset gid_num.
-                    // Then aggregate lang codes and recreate the collecting table.
+            let (lang_part, region) = match parse_lang_tag(&lang_code) {
+                Some((l, r)) => (Some(l), r),
+                None => (None, None),
+            };
 
-                    dv.store_data(&pool).await?;
-                    dv = AltRecVecs::new(vector_size);
-                    gid_num = 0;
-                    transfer_data(&pool).await?;
-                    create_collecting_table(&pool).await?;
-                }
-                
-                old_gid = geo_id;
-            }
-            
             let alt_name = AltRec {
                 geo_id: source.geoname_id,
-                name: source.alternate_name.trim().replace(".", "").replace("'", "’"),
+                name: rules.normalise(&source.alternate_name),
+                orig_name: source.alternate_name,
                 lang: lang_code.clone(),
+                lang_code: lang_part,
+                region,
+                is_preferred: source.is_preferred_name == Some(1),
+                is_short,
+                is_colloquial: source.is_colloquial == Some(1),
+                is_historic: source.is_historic == Some(1),
+                is_transliterated: false,
+                yfrom: parse_year(&source.yfrom),
+                yto: parse_year(&source.yto),
             };
 
-            // transfer data to vectors
-            dv.add_data(&alt_name);
+            send(names.add(&alt_name))?;
         }
         
         i +=1;
@@ -128,11 +351,44 @@ pub async fn import_alt_name_data(data_folder: &PathBuf, source_file_name: &str,
         }
     }
 
-    dv.store_data(&pool).await?;
-    transfer_data(&pool).await?;
-    info!("Processed {} alternate name records in total", i);
-            
-    Ok(())
+    csv_rdr.get_ref().log_skipped_lines(source_file_name);
+    send(names.take())?;
+    send(codes.take())?;
+    send(ext_ids.take())?;
+
+    Ok(ParseStats {
+        read: i,
+        not_kept,
+        transliterated,
+        excluded,
+    })
+}
+
+
+fn log_load_rate(read: u64, written: u64, writer_tasks: usize, started: Instant) {
+
+    // Covers both reading the file and writing the selected rows.
+
+    let secs = started.elapsed().as_secs_f64();
+    let rate = if secs > 0.0 { (read as f64 / secs) as u64 } else { read };
+    info!("{} alternate name records read and {} rows written by {} tasks in {:.1} s ({} records per second)", 
+        read, written, writer_tasks, secs, rate);
+    record_count("alt name load seconds", secs.round() as u64);
+    record_count("alt name records per second", rate);
+}
+
+
+fn log_excluded_scripts(excluded: HashMap<Script, u64>) {
+
+    let mut counts: Vec<(Script, u64)> = excluded.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.full_name().cmp(b.0.full_name())));
+
+    let total: u64 = counts.iter().map(|c| c.1).sum();
+    info!("{} alternate names excluded because of their script", total);
+    for (script, n) in counts {
+        info!("    {}: {}", script.full_name(), n);
+        record_count(&format!("alt names excluded ({})", script.full_name()), n);
+    }
 }
 
 
@@ -143,7 +399,17 @@ async fn create_collecting_table(pool: &Pool<Postgres>) -> Result<PgQueryResult,
     (
         geo_id       int
       , alt_name     varchar
+      , orig_name    varchar
       , lang         varchar
+      , lang_code    varchar
+      , region       varchar
+      , is_preferred bool
+      , is_short     bool
+      , is_colloquial bool
+      , is_historic  bool
+      , is_transliterated bool
+      , yfrom        int
+      , yto          int
     );"#;
 
     sqlx::raw_sql(sql).execute(pool)
@@ -151,20 +417,225 @@ async fn create_collecting_table(pool: &Pool<Postgres>) -> Result<PgQueryResult,
 }
 
 
-async fn transfer_data(pool: &Pool<Postgres>) -> Result<PgQueryResult, AppError>  {
+async fn log_unresolved_langs(pool: &Pool<Postgres>) -> Result<(), AppError> {
+
+    let sql = r#"select count(distinct n.lang) from geo.alt_src_names n
+                 left join src_next.lang_codes c
+                 on n.lang_code = c.code
+                 where n.lang <> 'none' and c.code is null"#;
+
+    let unresolved: i64 = sqlx::query_scalar(sql)
+        .fetch_one(pool).await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    info!("{} distinct language tags could not be matched to a language, and are listed as given", unresolved);
+    record_count("unresolved language tags", unresolved as u64);
+
+    Ok(())
+}
+
+
+async fn drop_collecting_table(pool: &Pool<Postgres>) -> Result<PgQueryResult, AppError> {
+    
+    let sql = r#"drop table if exists geo.alt_src_names;"#;
+
+    sqlx::raw_sql(sql).execute(pool)
+        .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))
+}
+
 
-    let sql = r#"insert into geo.alt_names (id, alt_name, langs)
-        select geo_id, alt_name,
-	    string_agg(c.name, ', ')
-        from geo.alt_src_names n
-        left join 
-            (select * from src.lang_codes where code_type = '639-1') c
-        on n.lang = c.code
+async fn transfer_data(pool: &Pool<Postgres>) -> Result<(), AppError>  {
+
+    // A name may appear several times for the same geoname id, once for each
+    // language. It is treated as preferred, short or colloquial if any of those
+    // records say so, but as historic only if all of them do. The year range
+    // covers the earliest start and the latest end given, with no end year if
+    // any record gives none (i.e. the name is still in use). Languages are listed
+    // alphabetically, so that the list does not depend on the order of the file.
+
+    // The language part of each tag is matched against the 639-1, 639-2 and 639-3
+    // codes, with any region added in brackets (e.g. 'Chinese (CN)'). Tags that 
+    // cannot be matched are listed as given.
+
+    // The matched languages are also held as an array of codes, using the 639-1 
+    // code where there is one (so 'eng' and 'en-GB' both become 'en'). Unmatched 
+    // tags are not included in the array.
+
+    // Where several source names normalise to the same name, the first of 
+    // them (alphabetically) is kept as the original, preferring those that 
+    // were not transliterated. A name is marked as transliterated only if 
+    // it does not also appear in the file as a name in its own right.
+
+    let sql = r#"insert into geo.alt_names (id, alt_name, orig_name, langs, lang_codes, is_preferred, 
+        is_short, is_colloquial, is_historic, is_transliterated, yfrom, yto)
+        select geo_id, alt_name, 
+        coalesce(min(orig_name) filter (where not is_transliterated), min(orig_name)),
+	    string_agg(distinct lang_name, ', ' order by lang_name),
+        coalesce(array_agg(distinct iso_code order by iso_code) filter (where iso_code is not null), '{}'),
+        bool_or(is_preferred), bool_or(is_short), bool_or(is_colloquial), 
+        bool_and(is_historic), bool_and(is_transliterated), min(yfrom), 
+        case when bool_or(yto is null) then null else max(yto) end
+        from 
+            (select n.*, 
+             case when c.name is not null then c.name||coalesce(' ('||n.region||')', '')
+                  when n.lang <> 'none' then n.lang
+             end as lang_name,
+             coalesce(c1.code, c.code) as iso_code
+             from geo.alt_src_names n
+             left join src_next.lang_codes c
+             on n.lang_code = c.code
+             left join 
+                (select code, name from src_next.lang_codes where code_type = '639-1') c1
+             on c.name = c1.name) s
         group by geo_id, alt_name
         order by geo_id, alt_name"#;
 
+    let res = sqlx::raw_sql(sql).execute(pool)
+        .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    info!("{} aggregated alternate name records created in geo.alt_names", res.rows_affected());
+    record_count("alt names aggregated", res.rows_affected());
+
+    Ok(())
+}
+
+
+async fn verify_alt_names(pool: &Pool<Postgres>) -> Result<(), AppError>  {
+
+    // Each (id, alt_name) pair should now appear only once.
+
+    let sql = r#"select count(*) from 
+        (select id, alt_name from geo.alt_names
+         group by id, alt_name
+         having count(*) > 1) d"#;
+
+    let dups: i64 = sqlx::query_scalar(sql)
+        .fetch_one(pool).await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    if dups == 0 {
+        info!("Verified that geo.alt_names has no duplicate (id, alt_name) records");
+    }
+    else {
+        warn!("{} (id, alt_name) pairs are duplicated in geo.alt_names", dups);
+    }
+    record_count("duplicate alt name pairs", dups as u64);
+
+    Ok(())
+}
+
+        
+
+async fn dedupe_place_codes(pool: &Pool<Postgres>) -> Result<(), AppError>  {
+
+    // The same code is sometimes listed more than once for a place.
+
+    let sql = r#"delete from src_next.place_codes a
+        using src_next.place_codes b
+        where a.geo_id = b.geo_id
+        and a.code_type = b.code_type
+        and a.code = b.code
+        and a.id > b.id"#;
+
     sqlx::raw_sql(sql).execute(pool)
-        .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))
+        .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    let sql = r#"select code_type::text, count(*) from src_next.place_codes
+                 group by code_type order by code_type"#;
+
+    let counts: Vec<(String, i64)> = sqlx::query_as(sql)
+        .fetch_all(pool).await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    for (code_type, n) in counts {
+        info!("{} {} codes stored in src.place_codes", n, code_type);
+        record_count(&format!("place codes ({})", code_type), n as u64);
+    }
+
+    Ok(())
 }
 
-        
\ No newline at end of file
+
+async fn dedupe_external_ids(pool: &Pool<Postgres>) -> Result<(), AppError>  {
+
+    let sql = r#"delete from src_next.external_ids a
+        using src_next.external_ids b
+        where a.geo_id = b.geo_id
+        and a.ext_id = b.ext_id
+        and a.id > b.id"#;
+
+    sqlx::raw_sql(sql).execute(pool)
+        .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    let sql = r#"select id_type::text, count(*) from src_next.external_ids
+                 group by id_type order by id_type"#;
+
+    let counts: Vec<(String, i64)> = sqlx::query_as(sql)
+        .fetch_all(pool).await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    for (id_type, n) in counts {
+        info!("{} {} ids stored in src.external_ids", n, id_type);
+        record_count(&format!("external ids ({})", id_type), n as u64);
+    }
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_batch() -> CopyBatch {
+        let mut builder = BatchBuilder::new("src_next.place_codes", &["geo_id", "code_type", "code"]);
+        builder.add(&PlaceCode { geo_id: 2643743, code_type: "iata".to_string(), code: "LON".to_string() });
+        builder.take().unwrap()
+    }
+
+    #[tokio::test]
+    async fn check_writer_error_stops_parser_and_writers() {
+        let (tx, rx) = mpsc::channel::<CopyBatch>(4);
+        let rx = Arc::new(Mutex::new(rx));
+        let failed = Arc::new(AtomicBool::new(false));
+        for _ in 0..3 {
+            tx.send(test_batch()).await.unwrap();
+        }
+
+        let res = run_writer(Arc::clone(&rx), Arc::clone(&failed), |_| async {
+            Err(AppError::TaskError("copy failed".to_string()))
+        }).await;
+        assert!(res.is_err());
+        assert!(failed.load(Ordering::Relaxed));
+
+        // The other writers write none of the queued batches
+
+        let written = run_writer(Arc::clone(&rx), Arc::clone(&failed), |_| async { Ok(1) }).await.unwrap();
+        assert_eq!(written, 0);
+
+        // and the parser stops at its next send, both from the flag
+        // and because the channel has been closed.
+
+        let parser = tokio::task::spawn_blocking(move || {
+            (send_batch(&tx, &failed, Some(test_batch())), send_batch(&tx, &AtomicBool::new(false), Some(test_batch())))
+        });
+        let (flagged, closed) = parser.await.unwrap();
+        assert!(flagged.is_err());
+        assert!(closed.is_err());
+    }
+
+    #[tokio::test]
+    async fn check_writers_load_all_batches_without_errors() {
+        let (tx, rx) = mpsc::channel::<CopyBatch>(4);
+        let rx = Arc::new(Mutex::new(rx));
+        let failed = Arc::new(AtomicBool::new(false));
+        for _ in 0..3 {
+            tx.send(test_batch()).await.unwrap();
+        }
+        drop(tx);
+
+        let written = run_writer(rx, Arc::clone(&failed), |_| async { Ok(1) }).await.unwrap();
+        assert_eq!(written, 3);
+        assert!(!failed.load(Ordering::Relaxed));
+    }
+}
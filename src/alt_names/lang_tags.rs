@@ -0,0 +1,60 @@
+/***************************************************************************
+ * Splits the language tags used in the alternate names file into their
+ * language and region parts. Most tags are simple ISO 639 codes (en, deu),
+ * but some follow BCP-47 (zh-CN, en-GB, sr-Latn-RS), and a few carry
+ * other qualifiers (fr_1793). Script and other subtags are discarded.
+ ***************************************************************************/
+
+
+pub fn parse_lang_tag(tag: &str) -> Option<(String, Option<String>)> {
+
+    // Returns None if the tag does not start with a plausible
+    // 2 or 3 letter language code - such tags are kept only as given.
+
+    let mut subtags = tag.trim().split(['-', '_']);
+
+    let language = subtags.next()?;
+    if !(2..=3).contains(&language.len()) || !language.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+
+    // The region, if present, is 2 letters (GB) or 3 digits (419),
+    // and may follow a 4 letter script subtag.
+
+    let region = subtags
+        .find(|s| !(s.len() == 4 && s.chars().all(|c| c.is_ascii_alphabetic())))
+        .filter(|s| (s.len() == 2 && s.chars().all(|c| c.is_ascii_alphabetic()))
+                 || (s.len() == 3 && s.chars().all(|c| c.is_ascii_digit())))
+        .map(|s| s.to_uppercase());
+
+    Some((language.to_lowercase(), region))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_simple_codes_parsed() {
+        assert_eq!(parse_lang_tag("en"), Some(("en".to_string(), None)));
+        assert_eq!(parse_lang_tag("deu"), Some(("deu".to_string(), None)));
+        assert_eq!(parse_lang_tag("fr_1793"), Some(("fr".to_string(), None)));
+    }
+
+    #[test]
+    fn check_region_codes_parsed() {
+        assert_eq!(parse_lang_tag("zh-CN"), Some(("zh".to_string(), Some("CN".to_string()))));
+        assert_eq!(parse_lang_tag("en-gb"), Some(("en".to_string(), Some("GB".to_string()))));
+        assert_eq!(parse_lang_tag("sr-Latn-RS"), Some(("sr".to_string(), Some("RS".to_string()))));
+        assert_eq!(parse_lang_tag("es-419"), Some(("es".to_string(), Some("419".to_string()))));
+        assert_eq!(parse_lang_tag("zh-Hant"), Some(("zh".to_string(), None)));
+    }
+
+    #[test]
+    fn check_invalid_tags_rejected() {
+        assert_eq!(parse_lang_tag(""), None);
+        assert_eq!(parse_lang_tag("1793"), None);
+        assert_eq!(parse_lang_tag("piny"), None);
+    }
+}
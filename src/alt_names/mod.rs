@@ -1,9 +1,15 @@
-mod data_vectors;
 mod import;
+mod external_ids;
+mod lang_tags;
+mod scripts;
+mod transliterate;
 
 use sqlx::{Pool, Postgres};
 use crate::AppError;
 use std::path::PathBuf;
+use unicode_script::Script;
+use std::collections::HashSet;
+use crate::normalise::NameRules;
 
 
 pub async fn create_alt_name_table(pool: &Pool<Postgres>) -> Result<(), AppError> {
@@ -13,9 +19,41 @@ pub async fn create_alt_name_table(pool: &Pool<Postgres>) -> Result<(), AppError
     (
         id           int   
       , alt_name 	 varchar  
+      , orig_name    varchar
       , langs        varchar
+      , lang_codes   text[]
+      , is_preferred bool
+      , is_short     bool
+      , is_colloquial bool
+      , is_historic  bool
+      , is_transliterated bool
+      , yfrom        int
+      , yto          int
     );
-    create index alt_names_idx on geo.alt_names(id);"#;
+    create index alt_names_idx on geo.alt_names(id);
+
+    drop table if exists src_next.place_codes;
+    create table src_next.place_codes
+    (
+        id           int primary key generated always as identity
+      , geo_id       int
+      , code_type    varchar
+      , code         varchar
+    );
+    create index place_codes_geo_id on src_next.place_codes(geo_id);
+    create index place_codes_code on src_next.place_codes(code);
+
+    drop table if exists src_next.external_ids;
+    create table src_next.external_ids
+    (
+        id           int primary key generated always as identity
+      , geo_id       int
+      , id_type      varchar
+      , lang         varchar
+      , ext_id       varchar
+    );
+    create index external_ids_geo_id on src_next.external_ids(geo_id);
+    create index external_ids_ext_id on src_next.external_ids(ext_id);"#;
    
     sqlx::raw_sql(sql).execute(pool)
         .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
@@ -24,8 +62,10 @@ pub async fn create_alt_name_table(pool: &Pool<Postgres>) -> Result<(), AppError
 }
 
 
-pub async fn import_data(data_folder: &PathBuf, source_file_name: &str, pool: &Pool<Postgres>, latin_only: bool) -> Result<(), AppError> {
+pub async fn import_data(source_file: &PathBuf, pool: &Pool<Postgres>, scripts: Option<&[Script]>, 
+                         transliterate: bool, rules: &NameRules, kept_ids: HashSet<i64>, 
+                         writer_tasks: usize) -> Result<(), AppError> {
 
-    import::import_alt_name_data(data_folder, source_file_name, pool, latin_only).await
+    import::import_alt_name_data(source_file, pool, scripts, transliterate, rules, kept_ids, writer_tasks).await
 
 }
@@ -0,0 +1,44 @@
+/***************************************************************************
+ * Classifies alternate names by Unicode script, so that only names in the
+ * configured scripts are retained. Characters common to many scripts
+ * (digits, punctuation, spaces) and combining marks are ignored, so that
+ * e.g. "Ébreuil", "van Buren" and "'s-Hertogenbosch" all count as Latin.
+ ***************************************************************************/
+
+use unicode_script::{Script, UnicodeScript};
+
+
+pub fn excluded_script(name: &str, scripts: &[Script]) -> Option<Script> {
+
+    // Returns the first script used in the name that is not in the list
+    // to be kept, or None if the name can be retained.
+
+    name.chars()
+        .map(|c| c.script())
+        .find(|s| !matches!(s, Script::Common | Script::Inherited | Script::Unknown) && !scripts.contains(s))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_latin_names_retained() {
+        let latin = [Script::Latin];
+        assert_eq!(excluded_script("Ébreuil", &latin), None);
+        assert_eq!(excluded_script("van Buren", &latin), None);
+        assert_eq!(excluded_script("'s-Hertogenbosch", &latin), None);
+        assert_eq!(excluded_script("Ağrı", &latin), None);
+        assert_eq!(excluded_script("1st Street", &latin), None);
+    }
+
+    #[test]
+    fn check_non_latin_names_identified() {
+        let latin = [Script::Latin];
+        assert_eq!(excluded_script("Москва", &latin), Some(Script::Cyrillic));
+        assert_eq!(excluded_script("10 東京", &latin), Some(Script::Han));
+        assert_eq!(excluded_script("(Αθήνα)", &latin), Some(Script::Greek));
+        assert_eq!(excluded_script("Москва", &[Script::Latin, Script::Cyrillic]), None);
+    }
+}
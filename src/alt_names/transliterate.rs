@@ -0,0 +1,86 @@
+/***************************************************************************
+ * Transliteration of names in non-Latin scripts (Cyrillic, Greek, Han etc.)
+ * into ASCII, so that they can be found by users typing in Latin characters. 
+ * The deunicode tables are used, so no external service is needed. The 
+ * results are approximate (Han characters are given as pinyin syllables) 
+ * and are stored as aliases, marked as transliterated, rather than as names 
+ * in their own right.
+ ***************************************************************************/
+
+use unicode_script::{Script, UnicodeScript};
+
+// Characters that deunicode cannot transliterate are replaced by this
+// marker, and names including any of them are given no alias.
+
+const NO_MATCH: &str = "\u{0}";
+
+// Abjads normally leave their vowels unwritten, so deunicode gives only a 
+// consonant skeleton (e.g. 'lqhr' for Cairo) that nobody would search for.
+
+const ABJAD_SCRIPTS: [Script; 4] = [Script::Arabic, Script::Hebrew, Script::Syriac, Script::Thaana];
+
+
+pub fn latin_alias(name: &str) -> Option<String> {
+
+    // Only names with at least one letter in a script other than Latin
+    // are transliterated - the rest would be returned (almost) unchanged.
+
+    let non_latin = name.chars()
+        .map(|c| c.script())
+        .any(|s| !matches!(s, Script::Latin | Script::Common | Script::Inherited | Script::Unknown));
+    if !non_latin || name.chars().any(|c| ABJAD_SCRIPTS.contains(&c.script())) {
+        return None;
+    }
+
+    let ascii = deunicode::deunicode_with_tofu(name, NO_MATCH);
+    if ascii.contains(NO_MATCH) {
+        return None;
+    }
+
+    // Symbols used by some of the tables (e.g. '@') are dropped, and the
+    // spaces between syllables collapsed.
+
+    let kept: String = ascii.chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, ' ' | '-' | '\'' | '(' | ')' | ','))
+        .collect();
+    let alias = kept.split_whitespace().collect::<Vec<&str>>().join(" ");
+
+    match alias.chars().any(|c| c.is_ascii_alphabetic()) {
+        true => Some(alias),
+        false => None,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_non_latin_names_transliterated() {
+        assert_eq!(latin_alias("Москва"), Some("Moskva".to_string()));
+        assert_eq!(latin_alias("Αθήνα"), Some("Athena".to_string()));
+        assert_eq!(latin_alias("東京"), Some("Dong Jing".to_string()));
+    }
+
+    #[test]
+    fn check_abjad_names_not_transliterated() {
+        assert_eq!(latin_alias("القاهرة"), None);
+        assert_eq!(latin_alias("ירושלים"), None);
+        assert_eq!(latin_alias("ܐܘܪܗܝ"), None);
+        assert_eq!(latin_alias("މާލެ"), None);
+    }
+
+    #[test]
+    fn check_latin_names_not_transliterated() {
+        assert_eq!(latin_alias("Ébreuil"), None);
+        assert_eq!(latin_alias("'s-Hertogenbosch"), None);
+        assert_eq!(latin_alias("1st Street"), None);
+    }
+
+    #[test]
+    fn check_untransliterable_names_skipped() {
+        assert_eq!(latin_alias("𓀀"), None);
+        assert_eq!(latin_alias("١٢٣"), None);
+    }
+}
@@ -1,11 +1,12 @@
 use sqlx::{Pool, Postgres};
 use crate::AppError;
-use std::path::PathBuf;
-use std::io::BufReader;
-use std::fs::File;
-use csv::ReaderBuilder;
-use super::data_vectors::CityVecs;
+use std::path::{Path, PathBuf};
+use crate::setup::source_file::{file_name_of, SourceFile};
+use crate::setup::bulk_writer::{BulkWriter, CopyRow, RowWriter};
 use log::info;
+use crate::setup::config_reader::{CityPars, CitySource, EXCLUDED_CITY_CODES};
+use crate::setup::run_history::record_count;
+use crate::normalise::NameRules;
 
 #[derive(serde::Deserialize)]
 #[allow(dead_code)]
@@ -34,65 +35,75 @@ struct CityLine {
 pub struct CityRec {
     pub id: i64,
     pub name: String,
+    pub orig_name: String,
     pub disamb_type: String,
-    pub disamb_code: String,
+    pub admin1_code: Option<String>,
+    pub admin2_code: Option<String>,
+    pub admin3_code: Option<String>,
+    pub admin4_code: Option<String>,
     pub country_code: String,
     pub lat: Option<f64>,
     pub lng: Option<f64>,
     pub population: Option<i64>,
 }
 
+impl CopyRow for CityRec {
+    fn write_row(&self, row: &mut RowWriter) {
+        row.add(&self.id).add(&self.name).add(&self.orig_name).add(&self.disamb_type)
+           .add(&self.admin1_code).add(&self.admin2_code).add(&self.admin3_code).add(&self.admin4_code)
+           .add(&self.country_code).add(&self.lat).add(&self.lng).add(&self.population);
+    }
+}
+
 
-pub async fn import_cities_data(data_folder: &PathBuf, source_file_name: &str, pool: &Pool<Postgres>) -> Result<(), AppError> {
+pub async fn import_cities_data(source_file: &PathBuf, city_pars: &CityPars, rules: &NameRules, pool: &Pool<Postgres>) -> Result<(), AppError> {
 
-    let source_file_path: PathBuf = [data_folder, &PathBuf::from(source_file_name)].iter().collect();
-    let file = File::open(source_file_path)?;
-    let buf_reader = BufReader::new(file);
-    let mut csv_rdr = ReaderBuilder::new()
-        .has_headers(false)
-        .delimiter(9)
-        .from_reader(buf_reader);
+    let source_file_name = &file_name_of(source_file);
+    let mut source = SourceFile::open_source(source_file)?;
+    let mut csv_rdr = source.csv_reader(Some("geonameid"), false)?;
     
     let mut i = 0;
-    let vector_size = 10000;
-    let mut dv: CityVecs = CityVecs::new(vector_size);
+    let mut n = 0;
+    let mut bw = BulkWriter::new(pool, "geo.cities", &["id", "name", "orig_name", "disamb_type", 
+        "admin1_code", "admin2_code", "admin3_code", "admin4_code", "country_code", "lat", "lng", "pop"]);
 
-    let gb = "GB".to_string();
-    let fr = "FR".to_string();
             
     for result in csv_rdr.deserialize() {
     
         let source: CityLine = result?;
+        n +=1;
+
+        if !is_included_city(&source, city_pars) {
+            continue;
+        }
+
+        // Full admin codes are constructed here (e.g. GB.ENG, GB.ENG.GLA), to match 
+        // those in the admin tables. The disambiguation area is derived from them 
+        // later, using the admin level configured for the country.
+
+        let (country_code, disamb_type) = match source.country_code {
+            Some(s) => {
+                let disamb_type = format!("admin{}", city_pars.disamb_level(&s));
+                (s, disamb_type)
+            },
+            None => ("none".to_string(), "none".to_string()),
+        };
 
-        let mut disamb_type = "none".to_string();
-        let mut disamb_code = "none".to_string();
-
-        let country_code = match source.country_code {
-           Some(s) => {
-                disamb_type = "admin1".to_string();
-                disamb_code = match source.admin1_code {
-                    Some(s1) => s.clone() + "." + &s1,
-                    None => "none".to_string(),
-                };
-
-                if s == gb || s == fr {
-                disamb_type = "admin2".to_string();
-                disamb_code = match source.admin2_code {
-                    Some(s2) => disamb_code + "." + &s2,
-                    None => "none".to_string(),
-                    };
-                }
-                s
-           },
-           None => "none".to_string(),
+        let admin1_code = source.admin1_code.map(|a1| format!("{}.{}", country_code, a1));
+        let admin2_code = match (&admin1_code, source.admin2_code) {
+            (Some(a1), Some(a2)) => Some(format!("{}.{}", a1, a2)),
+            _ => None,
         };
-        
 
         let city_rec = CityRec {
             id: source.geonameid,
-            name: source.name.replace("'", "’"),
+            name: rules.normalise(&source.name),
+            orig_name: source.name,
             disamb_type: disamb_type,
-            disamb_code: disamb_code,
+            admin1_code: admin1_code,
+            admin2_code: admin2_code,
+            admin3_code: source.admin3_code,
+            admin4_code: source.admin4_code,
             country_code: country_code,
             lat: source.latitude,
             lng: source.longitude,
@@ -100,13 +111,91 @@ pub async fn import_cities_data(data_folder: &PathBuf, source_file_name: &str, p
         };
 
 
-        dv.add_data(&city_rec);   // transfer data to vectors
+        bw.add(&city_rec).await?;   // allCountries can supply millions of records
         i +=1;    
 
     }
             
-    dv.store_data(&pool).await?;
-    info!("{} records processed from {} to geo.cities", i, source_file_name);
+    csv_rdr.get_ref().log_skipped_lines(source_file_name);
+    bw.finish().await?;
+    info!("{} of {} records processed from {} to geo.cities ({} filtered out)", i, n, source_file_name, n - i);
+    record_count("city records filtered out", n - i);
+    record_count("city source records", i);
 
     Ok(())
-}
\ No newline at end of file
+}
+
+
+pub fn read_city_ids(source_file: &Path, city_pars: &CityPars) -> Result<Vec<i64>, AppError> {
+
+    // The ids of the cities that import_cities_data will import.
+
+    let mut source = SourceFile::open_source(source_file)?;
+    let mut csv_rdr = source.csv_reader(Some("geonameid"), false)?;
+
+    let mut ids = Vec::new();
+    for result in csv_rdr.deserialize() {
+        let source: CityLine = result?;
+        if is_included_city(&source, city_pars) {
+            ids.push(source.geonameid);
+        }
+    }
+    Ok(ids)
+}
+
+
+fn is_included_city(source: &CityLine, city_pars: &CityPars) -> bool {
+
+    // The citiesN files only hold populated places, but allCountries holds
+    // every type of feature, and so needs to be filtered by feature class and code.
+    // Unless codes are configured the citiesN files are taken as they are.
+
+    if source.feature_class.as_deref() != Some("P") {
+        return false;
+    }
+
+    let feature_code = source.feature_code.as_deref().unwrap_or("");
+    let code_ok = match &city_pars.feature_codes {
+        Some(codes) => codes.iter().any(|c| c == feature_code),
+        None => city_pars.source != CitySource::AllCountries || !EXCLUDED_CITY_CODES.contains(&feature_code),
+    };
+
+    code_ok && source.population.unwrap_or(0) >= city_pars.min_population
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn city_line(feature_code: &str, population: i64) -> CityLine {
+        CityLine {
+            geonameid: 1, name: "Test".to_string(), asciiname: "Test".to_string(), alternatenames: None,
+            latitude: None, longitude: None, feature_class: Some("P".to_string()), feature_code: Some(feature_code.to_string()),
+            country_code: Some("GB".to_string()), cc2: None, admin1_code: None, admin2_code: None, admin3_code: None,
+            admin4_code: None, population: Some(population), elevation: None, dem: None, timezone: None, mod_date: None,
+        }
+    }
+
+    fn city_pars(source: CitySource) -> CityPars {
+        CityPars {
+            source,
+            min_population: 0,
+            feature_codes: None,
+            disamb_levels: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn check_cities5000_sections_are_imported_by_default() {
+        assert!(is_included_city(&city_line("PPLX", 6000), &city_pars(CitySource::Cities5000)));
+        assert!(is_included_city(&city_line("PPL", 6000), &city_pars(CitySource::Cities5000)));
+    }
+
+    #[test]
+    fn check_all_countries_sections_are_excluded_by_default() {
+        assert!(!is_included_city(&city_line("PPLX", 6000), &city_pars(CitySource::AllCountries)));
+        assert!(is_included_city(&city_line("PPL", 6000), &city_pars(CitySource::AllCountries)));
+    }
+}
@@ -1,10 +1,12 @@
-mod data_vectors;
 mod import;
 
 use sqlx::{Pool, Postgres};
 use crate::AppError;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use log::info;
+use crate::setup::config_reader::CityPars;
+use crate::setup::run_history::record_count;
+use crate::normalise::{add_search_keys, NameRules};
 
 pub async fn create_city_tables(pool: &Pool<Postgres>) -> Result<(), AppError> {
 
@@ -14,10 +16,19 @@ pub async fn create_city_tables(pool: &Pool<Postgres>) -> Result<(), AppError> {
                 (
                     id                    int primary key
                   , name                  varchar
+                  , orig_name             varchar
                   , disamb_type           varchar
                   , disamb_id             int
                   , disamb_code           varchar
                   , disamb_name           varchar
+                  , admin1_id             int
+                  , admin1_code           varchar
+                  , admin1_name           varchar
+                  , admin2_id             int
+                  , admin2_code           varchar
+                  , admin2_name           varchar
+                  , admin3_code           varchar
+                  , admin4_code           varchar
                   , country_id            int
                   , country_code          varchar
                   , country_name          varchar
@@ -26,15 +37,24 @@ pub async fn create_city_tables(pool: &Pool<Postgres>) -> Result<(), AppError> {
                   , pop                   int
                 );
                 
-            drop table if exists src.cities;
-                create table src.cities
+            drop table if exists src_next.cities;
+                create table src_next.cities
                 (
                     id                    int primary key
                   , name                  varchar
+                  , orig_name             varchar
                   , disamb_type           varchar
                   , disamb_id             int
                   , disamb_code           varchar
                   , disamb_name           varchar
+                  , admin1_id             int
+                  , admin1_code           varchar
+                  , admin1_name           varchar
+                  , admin2_id             int
+                  , admin2_code           varchar
+                  , admin2_name           varchar
+                  , admin3_code           varchar
+                  , admin4_code           varchar
                   , country_id            int
                   , country_code          varchar
                   , country_name          varchar
@@ -43,8 +63,8 @@ pub async fn create_city_tables(pool: &Pool<Postgres>) -> Result<(), AppError> {
                   , pop                   int
                 );
                 
-            drop table if exists src.city_names;
-            create table src.city_names
+            drop table if exists src_next.city_names;
+            create table src_next.city_names
             (
                   id                    int PRIMARY KEY GENERATED BY DEFAULT AS IDENTITY (START WITH 1000001 INCREMENT BY 1) NOT NULL
                 , city_id               int
@@ -54,10 +74,22 @@ pub async fn create_city_tables(pool: &Pool<Postgres>) -> Result<(), AppError> {
                 , country_id            int
                 , country_name          varchar
                 , alt_name              varchar
+                , orig_alt_name         varchar
+                , search_key            varchar
                 , langlist              varchar
+                , lang_codes            text[] default '{}'
+                , is_preferred          bool default false
+                , is_short              bool default false
+                , is_colloquial         bool default false
+                , is_historic           bool default false
+                , is_transliterated     bool default false
+                , yfrom                 int
+                , yto                   int
             );
-            create index city_names_city_id on src.city_names(city_id);
-            create index city_names_alt_name on src.city_names(alt_name);"#;
+            create index city_names_city_id on src_next.city_names(city_id);
+            create index city_names_alt_name on src_next.city_names(alt_name);
+            create index city_names_lang_codes on src_next.city_names using gin(lang_codes);
+            create index city_names_search_key on src_next.city_names(search_key);"#;
 
     sqlx::raw_sql(sql).execute(pool)
             .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
@@ -66,28 +98,38 @@ pub async fn create_city_tables(pool: &Pool<Postgres>) -> Result<(), AppError> {
 }
 
 
-pub async fn import_data(data_folder: &PathBuf, source_file_name: &str, pool: &Pool<Postgres>) -> Result<(), AppError> {
+pub async fn import_data(source_file: &PathBuf, city_pars: &CityPars, rules: &NameRules, pool: &Pool<Postgres>) -> Result<(), AppError> {
 
-    import::import_cities_data(data_folder, source_file_name, pool).await?;
+    import::import_cities_data(source_file, city_pars, rules, pool).await?;
     transfer_cities_data(pool).await?;
     update_cities_data(pool).await?;
     update_orphaned_cities_data(pool).await?;
     remove_dup_cities_1(pool).await?;
     remove_dup_cities_2(pool).await?;
+    add_city_locations(pool).await?;
 
     create_city_names(pool).await?;
     add_missing_city_names(pool).await?;
     delete_dup_city_names(pool).await?;
+    add_search_keys("city_names", "alt_name", pool).await?;
 
     Ok(())
 }
 
 
+pub fn read_ids(source_file: &Path, city_pars: &CityPars) -> Result<Vec<i64>, AppError> {
+
+    import::read_city_ids(source_file, city_pars)
+}
+
+
 async fn transfer_cities_data(pool: &Pool<Postgres>) -> Result<(), AppError> {
 
-    let sql = r#"insert into src.cities(id, name, disamb_type, disamb_id, disamb_code, disamb_name, 
+    let sql = r#"insert into src_next.cities(id, name, orig_name, disamb_type, 
+    admin1_code, admin2_code, admin3_code, admin4_code,
     country_id, country_code, country_name, lat, lng, pop)
-    select id, name, disamb_type, disamb_id, disamb_code, disamb_name, 
+    select id, name, orig_name, disamb_type, 
+    admin1_code, admin2_code, admin3_code, admin4_code,
     country_id, country_code, country_name, lat, lng, pop
     from geo.cities"#;
 
@@ -95,6 +137,7 @@ async fn transfer_cities_data(pool: &Pool<Postgres>) -> Result<(), AppError> {
         .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
 
     info!("{} city records transferred to geo schema", res.rows_affected());
+    record_count("cities transferred", res.rows_affected());
 
     Ok(())
 }
@@ -102,52 +145,64 @@ async fn transfer_cities_data(pool: &Pool<Postgres>) -> Result<(), AppError> {
 
 async fn update_cities_data(pool: &Pool<Postgres>) -> Result<(), AppError> {
 
-    let sql = r#"update src.cities g
+    let sql = r#"update src_next.cities g
                  set country_id = null
                  where country_code = 'none'"#;
 
     sqlx::raw_sql(sql).execute(pool)
         .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
 
-    let sql = r#"update src.cities g
+    let sql = r#"update src_next.cities g
                  set country_id = c.id,
                  country_name = c.country_name
-                 from src.countries c
+                 from src_next.countries c
                  where g.country_code = c.iso_code"#;
 
     sqlx::raw_sql(sql).execute(pool)
         .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
 
-    let sql = r#"update src.cities g
-                set disamb_code = null
-                where disamb_code = 'none'"#;
-
-    sqlx::raw_sql(sql).execute(pool)
-        .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
-
-    let sql = r#"update src.cities c
-                 set disamb_id = a.id,
-                 disamb_name = a.name 
+    let sql = r#"update src_next.cities c
+                 set admin1_id = a.id,
+                 admin1_name = a.name 
                  from geo.adm1s a
-                 where c.disamb_code = a.code
-                 and c.disamb_type = 'admin1'"#;
+                 where c.admin1_code = a.code"#;
 
     let res = sqlx::raw_sql(sql).execute(pool)
         .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
 
     info!("{} city records updated with admin1 details", res.rows_affected());
+    record_count("cities with admin1 details", res.rows_affected());
 
-    let sql = r#"update src.cities c
-                 set disamb_id = a.id,
-                 disamb_name = a.name 
+    let sql = r#"update src_next.cities c
+                 set admin2_id = a.id,
+                 admin2_name = a.name 
                  from geo.adm2s a
-                 where c.disamb_code = a.code
-                 and c.disamb_type = 'admin2'"#;
+                 where c.admin2_code = a.code"#;
 
     let res = sqlx::raw_sql(sql).execute(pool)
         .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
 
     info!("{} city records updated with admin2 details", res.rows_affected());
+    record_count("cities with admin2 details", res.rows_affected());
+
+    // The disambiguation area is then taken from the admin level configured for 
+    // the country. Cities whose admin2 area is not listed, in countries disambiguated 
+    // by admin2, are left without one, and so are not included in the dedupe steps below.
+
+    let sql = r#"update src_next.cities
+                 set disamb_id = admin1_id,
+                 disamb_code = admin1_code,
+                 disamb_name = admin1_name
+                 where disamb_type = 'admin1';
+
+                 update src_next.cities
+                 set disamb_id = admin2_id,
+                 disamb_code = admin2_code,
+                 disamb_name = admin2_name
+                 where disamb_type = 'admin2';"#;
+
+    sqlx::raw_sql(sql).execute(pool)
+        .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
 
     Ok(())
 }
@@ -158,14 +213,14 @@ async fn update_orphaned_cities_data(pool: &Pool<Postgres>) -> Result<(), AppErr
     // As Hong Kong and Macao have been removed from the countries list, their cities
     // need to be re-assigned to China.
 
-    let sql = r#"update src.cities
+    let sql = r#"update src_next.cities
                 set disamb_name = disamb_name||' (HK)',
                 country_id = 1814991,
                 country_code = 'CN',
                 country_name = 'China'
                 where country_code = 'HK';
 
-                update src.cities
+                update src_next.cities
                 set disamb_name = disamb_name||' (Macao)',
                 country_id = 1814991,
                 country_code = 'CN',
@@ -185,33 +240,33 @@ async fn remove_dup_cities_1(pool: &Pool<Postgres>) -> Result<(), AppError> {
     // In initial method, the city with the greater population is retained
 
     let sql = r#"SET client_min_messages TO WARNING; 
-        drop table if exists src.temp_city_dups;
-        create table src.temp_city_dups as
+        drop table if exists src_next.temp_city_dups;
+        create table src_next.temp_city_dups as
         select  
             name, country_name, disamb_name, count(id), true as to_drop
-            from src.cities 
+            from src_next.cities 
             group by name, disamb_name, country_name
             having count(id) > 1;
 
-        drop table if exists src.temp_city_dup_recs;
-        create table src.temp_city_dup_recs as
+        drop table if exists src_next.temp_city_dup_recs;
+        create table src_next.temp_city_dup_recs as
         select  
             g.*, true as to_drop
-            from src.cities g
-            inner join src.temp_city_dups d
+            from src_next.cities g
+            inner join src_next.temp_city_dups d
             on g.name = d.name
             and g.disamb_name = d.disamb_name
             and g.country_name = d.country_name;
 
-        drop table if exists src.temp_max_pop_dups;
-        create table src.temp_max_pop_dups as 
+        drop table if exists src_next.temp_max_pop_dups;
+        create table src_next.temp_max_pop_dups as 
         select name, disamb_name, country_name, max(pop) as maxpop
-        from src.temp_city_dup_recs
+        from src_next.temp_city_dup_recs
         group by name, disamb_name, country_name;
 
-        update src.temp_city_dup_recs d
+        update src_next.temp_city_dup_recs d
         set to_drop = false
-        from src.temp_max_pop_dups m
+        from src_next.temp_max_pop_dups m
         where d.name = m.name
         and d.disamb_name = m.disamb_name
         and d.country_name = m.country_name
@@ -220,8 +275,8 @@ async fn remove_dup_cities_1(pool: &Pool<Postgres>) -> Result<(), AppError> {
     sqlx::raw_sql(sql).execute(pool)
         .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
 
-    let sql = r#"delete from src.cities c
-        using src.temp_city_dup_recs d
+    let sql = r#"delete from src_next.cities c
+        using src_next.temp_city_dup_recs d
         where c.id = d.id
         and d.to_drop = true;"#;
 
@@ -229,15 +284,16 @@ async fn remove_dup_cities_1(pool: &Pool<Postgres>) -> Result<(), AppError> {
         .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
 
     let sql = r#"SET client_min_messages TO WARNING; 
-        drop table if exists src.temp_city_dup_recs;
-        drop table if exists src.temp_max_pop_dups;
-        drop table if exists src.temp_city_dups;"#;
+        drop table if exists src_next.temp_city_dup_recs;
+        drop table if exists src_next.temp_max_pop_dups;
+        drop table if exists src_next.temp_city_dups;"#;
 
     sqlx::raw_sql(sql).execute(pool)
         .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
 
     info!("{} duplicated city records (name, area, country) with lower populations deleted", 
         res.rows_affected());
+    record_count("duplicate cities deleted (lower population)", res.rows_affected());
     
     Ok(())
 }
@@ -250,62 +306,113 @@ async fn remove_dup_cities_2(pool: &Pool<Postgres>) -> Result<(), AppError> {
     // minimum id is removed.
 
     let sql = r#"SET client_min_messages TO WARNING; 
-            drop table if exists src.temp_city_dups;
-            create table src.temp_city_dups as
+            drop table if exists src_next.temp_city_dups;
+            create table src_next.temp_city_dups as
             select  
                 name, country_name, disamb_name, count(id), true as to_drop
-                from src.cities 
+                from src_next.cities 
                 group by name, disamb_name, country_name
                 having count(id) > 1;
 
-            drop table if exists src.temp_city_dup_recs;
-            create table src.temp_city_dup_recs as
+            drop table if exists src_next.temp_city_dup_recs;
+            create table src_next.temp_city_dup_recs as
             select  
                 g.*, true as to_drop
-                from src.cities g
-                inner join src.temp_city_dups d
+                from src_next.cities g
+                inner join src_next.temp_city_dups d
                 on g.name = d.name
                 and g.disamb_name = d.disamb_name
                 and g.country_name = d.country_name;
 
-            drop table if exists src.temp_min_id_dups;
-            create table src.temp_min_id_dups as 
+            drop table if exists src_next.temp_min_id_dups;
+            create table src_next.temp_min_id_dups as 
             select name, disamb_name, country_name, min(id) as minid
-            from src.temp_city_dup_recs
+            from src_next.temp_city_dup_recs
             group by name, disamb_name, country_name;"#;
 
     sqlx::raw_sql(sql).execute(pool)
             .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
 
-    let sql = r#"delete from src.cities c
-            using src.temp_min_id_dups d
+    let sql = r#"delete from src_next.cities c
+            using src_next.temp_min_id_dups d
             where c.id = d.minid"#;
 
     let res = sqlx::raw_sql(sql).execute(pool)
         .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
 
     let sql = r#"SET client_min_messages TO WARNING; 
-            drop table if exists src.temp_city_dup_recs;
-            drop table if exists src.temp_min_id_dups;
-            drop table if exists src.temp_city_dups;"#;
+            drop table if exists src_next.temp_city_dup_recs;
+            drop table if exists src_next.temp_min_id_dups;
+            drop table if exists src_next.temp_city_dups;"#;
 
     sqlx::raw_sql(sql).execute(pool)
         .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
 
     info!("{} duplicated city records (name, area, country, population) with lower id deleted", 
         res.rows_affected());
+    record_count("duplicate cities deleted (lower id)", res.rows_affected());
+
+    Ok(())
+}
+
+
+async fn add_city_locations(pool: &Pool<Postgres>) -> Result<(), AppError> {
+
+    // If PostGIS is installed each city is also given a geography point, with a 
+    // spatial index, to support radius and nearest neighbour queries. Otherwise 
+    // the lat and lng columns are left as the only location data.
+
+    if !postgis_available(pool).await? {
+        info!("PostGIS not installed - city locations held only as lat and lng columns");
+        return Ok(());
+    }
+
+    let sql = r#"alter table src_next.cities add column location geography(Point, 4326);"#;
+
+    sqlx::raw_sql(sql).execute(pool)
+        .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    let sql = r#"update src_next.cities
+                set location = ST_SetSRID(ST_MakePoint(lng, lat), 4326)::geography
+                where lat is not null and lng is not null;"#;
+
+    let res = sqlx::raw_sql(sql).execute(pool)
+        .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    let sql = r#"create index cities_location on src_next.cities using gist(location);"#;
+
+    sqlx::raw_sql(sql).execute(pool)
+        .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    info!("PostGIS installed - {} city locations added as geography points, with a GiST index", res.rows_affected());
+    record_count("city locations added", res.rows_affected());
 
     Ok(())
 }
 
 
+async fn postgis_available(pool: &Pool<Postgres>) -> Result<bool, AppError> {
+
+    // The extension is not created here, as that needs elevated privileges 
+    // and adds objects to the public schema - it must already be installed.
+
+    let sql = r#"select exists (select 1 from pg_extension where extname = 'postgis')"#;
+
+    sqlx::query_scalar(sql)
+        .fetch_one(pool).await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))
+}
+
+
 async fn create_city_names(pool: &Pool<Postgres>) -> Result<(), AppError> {
     
-    let sql = r#"insert into src.city_names (city_id, city_name, disamb_id, 
-         disamb_name, country_id, country_name, alt_name, langlist)
+    let sql = r#"insert into src_next.city_names (city_id, city_name, disamb_id, 
+         disamb_name, country_id, country_name, alt_name, orig_alt_name, langlist, lang_codes,
+         is_preferred, is_short, is_colloquial, is_historic, is_transliterated, yfrom, yto)
          select c.id, c.name, c.disamb_id, c.disamb_name, c.country_id, 
-         c.country_name, a.alt_name, a.langs
-         from src.cities c 
+         c.country_name, a.alt_name, a.orig_name, a.langs, a.lang_codes,
+         a.is_preferred, a.is_short, a.is_colloquial, a.is_historic, a.is_transliterated, a.yfrom, a.yto
+         from src_next.cities c 
          inner join geo.alt_names a
          on c.id = a.id;"#;
 
@@ -313,6 +420,7 @@ async fn create_city_names(pool: &Pool<Postgres>) -> Result<(), AppError> {
     .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
 
     info!("{} city name records created", res.rows_affected());
+    record_count("city names created", res.rows_affected());
 
     Ok(())
 }
@@ -325,33 +433,36 @@ async fn add_missing_city_names(pool: &Pool<Postgres>) -> Result<(), AppError> {
     // names are in the city_names table
 
     let sql = r#"SET client_min_messages TO WARNING; 
-         drop table if exists src.temp_city_match;
+         drop table if exists src_next.temp_city_match;
 
-         create table src.temp_city_match as 
+         create table src_next.temp_city_match as 
          select *
-         from src.city_names
+         from src_next.city_names
          where city_name = alt_name;"#;
 
     sqlx::raw_sql(sql).execute(pool)
          .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
      
-    let sql = r#"insert into src.city_names
+    let sql = r#"insert into src_next.city_names
          (city_id, city_name, disamb_id, disamb_name, 
-         country_id, country_name, alt_name)
+         country_id, country_name, alt_name, orig_alt_name)
          select distinct n.city_id, n.city_name, 
          n.disamb_id, n.disamb_name, n.country_id, 
-         n.country_name, n.city_name as alt_name
-         from src.city_names n
-         left join src.temp_city_match m
+         n.country_name, n.city_name as alt_name, c.orig_name
+         from src_next.city_names n
+         inner join src_next.cities c
+         on n.city_id = c.id
+         left join src_next.temp_city_match m
          on n.city_id = m.city_id
          where m.id is null;
          
-         drop table src.temp_city_match;"#;
+         drop table src_next.temp_city_match;"#;
 
     let res = sqlx::raw_sql(sql).execute(pool)
     .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
 
     info!("{} missing city default names added to the city_names table", res.rows_affected());
+    record_count("missing city names added", res.rows_affected());
 
     Ok(())
 }
@@ -365,31 +476,32 @@ async fn delete_dup_city_names(pool: &Pool<Postgres>) -> Result<(), AppError> {
     // same as the official 'main; name (about a third of them).
 
     let sql = r#"SET client_min_messages TO WARNING; 
-            drop table if exists src.temp_dup_city_names;
+            drop table if exists src_next.temp_dup_city_names;
 
-            create table src.temp_dup_city_names
+            create table src_next.temp_dup_city_names
             as select  
             country_name, alt_name, count(id)
-            from src.city_names 
+            from src_next.city_names 
             group by country_name, alt_name
             having count(id) > 1"#;
 
     sqlx::raw_sql(sql).execute(pool)
         .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;    
 
-    let sql = r#"delete from src.city_names n
-            using src.temp_dup_city_names d
+    let sql = r#"delete from src_next.city_names n
+            using src_next.temp_dup_city_names d
             where d.country_name = n.country_name
             and d.alt_name = n.alt_name
             and d.alt_name <> n.city_name;
                         
-            drop table if exists src.temp_dup_city_names;
+            drop table if exists src_next.temp_dup_city_names;
             SET client_min_messages TO WARNING; "#;
 
     let res = sqlx::raw_sql(sql).execute(pool)
         .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
     
     info!("{} duplicate non-default city name records deleted (same country, name)", res.rows_affected());
+    record_count("duplicate city names deleted", res.rows_affected());
 
     Ok(())
 }
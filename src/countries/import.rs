@@ -1,11 +1,11 @@
 use sqlx::{Pool, Postgres};
 use crate::AppError;
-use std::path::PathBuf;
-use std::io::BufReader;
-use std::fs::File;
-use csv::ReaderBuilder;
-use super::data_vectors::CountryVecs;
+use std::path::{Path, PathBuf};
+use crate::setup::source_file::{file_name_of, SourceFile};
+use crate::setup::bulk_writer::{BulkWriter, CopyRow, RowWriter};
 use log::info;
+use crate::setup::run_history::record_count;
+use crate::normalise::NameRules;
 
 #[derive(serde::Deserialize)]
 #[allow(dead_code)]
@@ -39,26 +39,30 @@ pub struct CountryRec {
     pub rank: i32,
     pub iso_code: String,
     pub country_name: String,
+    pub orig_name: String,
     pub continent: String,
     pub tld: String,
     pub languages:  String,
     pub capital: String,
 }
 
+impl CopyRow for CountryRec {
+    fn write_row(&self, row: &mut RowWriter) {
+        row.add(&self.id).add(&self.rank).add(&self.iso_code).add(&self.country_name).add(&self.orig_name)
+           .add(&self.continent).add(&self.tld).add(&self.languages).add(&self.capital);
+    }
+}
+
 
-pub async fn import_countries_data(data_folder: &PathBuf, source_file_name: &str, pool: &Pool<Postgres>) -> Result<(), AppError> {
+pub async fn import_countries_data(source_file: &PathBuf, rules: &NameRules, pool: &Pool<Postgres>) -> Result<(), AppError> {
 
-    let source_file_path: PathBuf = [data_folder, &PathBuf::from(source_file_name)].iter().collect();
-    let file = File::open(source_file_path)?;
-    let buf_reader = BufReader::new(file);
-    let mut csv_rdr = ReaderBuilder::new()
-        .has_headers(false)
-        .delimiter(9)
-        .from_reader(buf_reader);
+    let source_file_name = &file_name_of(source_file);
+    let mut source = SourceFile::open_source(source_file)?;
+    let mut csv_rdr = source.csv_reader(Some("ISO"), false)?;
 
     let mut i = 0;
-    let vector_size = 500;
-    let mut dv: CountryVecs = CountryVecs::new(vector_size);
+    let mut bw = BulkWriter::new(pool, "geo.countries", 
+        &["id", "rank", "iso_code", "country_name", "orig_name", "continent", "tld", "languages", "capital"]);
             
     for result in csv_rdr.deserialize() {
     
@@ -73,21 +77,38 @@ pub async fn import_countries_data(data_folder: &PathBuf, source_file_name: &str
             id: source.geonameid,
             rank: rank,
             iso_code: source.iso,
-            country_name: source.country.trim().replace(".", "").replace("'", "’"),
+            country_name: rules.normalise(&source.country),
+            orig_name: source.country,
             continent: source.continent,
             tld: source.tld.unwrap_or("".to_string()),
             languages: source.languages.unwrap_or("".to_string()),
-            capital: source.capital.unwrap_or("".to_string()).trim().replace(".", "").replace("'", "’"),
+            capital: rules.normalise(&source.capital.unwrap_or("".to_string())),
         };
 
-        dv.add_data(&country_rec);   // transfer data to vectors
+        bw.add(&country_rec).await?;
         i +=1;    
 
     }
             
-    dv.store_data(&pool).await?;
+    csv_rdr.get_ref().log_skipped_lines(source_file_name);
+    bw.finish().await?;
     info!("{} records processed from {} to geo.countries", i, source_file_name);
+    record_count("country source records", i);
 
     Ok(())
 }
 
+
+
+pub fn read_country_ids(source_file: &Path) -> Result<Vec<i64>, AppError> {
+
+    let mut source = SourceFile::open_source(source_file)?;
+    let mut csv_rdr = source.csv_reader(Some("ISO"), false)?;
+
+    let mut ids = Vec::new();
+    for result in csv_rdr.deserialize() {
+        let source: CountryLine = result?;
+        ids.push(source.geonameid);
+    }
+    Ok(ids)
+}
@@ -1,10 +1,11 @@
-mod data_vectors;
 mod import;
 
 use sqlx::{Pool, Postgres};
 use crate::AppError;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use log::info;
+use crate::setup::run_history::record_count;
+use crate::normalise::{add_search_keys, NameRules};
 
 pub async fn create_country_tables(pool: &Pool<Postgres>) -> Result<(), AppError> {
 
@@ -15,6 +16,7 @@ pub async fn create_country_tables(pool: &Pool<Postgres>) -> Result<(), AppError
                     , rank                  int
                     , iso_code              varchar
                     , country_name          varchar
+                    , orig_name             varchar
                     , continent             varchar
                     , tld                   varchar
                     , languages             varchar
@@ -22,30 +24,43 @@ pub async fn create_country_tables(pool: &Pool<Postgres>) -> Result<(), AppError
                 );
             create index country_iso_code on geo.countries(iso_code);
             
-            drop table if exists src.countries;
-                create table src.countries
+            drop table if exists src_next.countries;
+                create table src_next.countries
                 (
                       id                    int  primary key
                     , rank                  int
                     , iso_code              varchar
                     , country_name          varchar
+                    , orig_name             varchar
                     , continent             varchar
                     , tld                   varchar
                     , languages             varchar
                     , capital               varchar
                 );
-            create index country_iso_code on src.countries(iso_code);
+            create index country_iso_code on src_next.countries(iso_code);
 
-            drop table if exists src.country_names;
-                create table src.country_names
+            drop table if exists src_next.country_names;
+                create table src_next.country_names
                 (
                       id                    int PRIMARY KEY GENERATED ALWAYS AS IDENTITY (START WITH 10001 INCREMENT BY 1) 
                     , country_id            int
                     , country_name          varchar
                     , alt_name              varchar
+                    , orig_alt_name         varchar
+                    , search_key            varchar
                     , langlist              varchar
+                    , lang_codes            text[] default '{}'
+                    , is_preferred          bool default false
+                    , is_short              bool default false
+                    , is_colloquial         bool default false
+                    , is_historic           bool default false
+                    , is_transliterated     bool default false
+                    , yfrom                 int
+                    , yto                   int
                 );
-            create index country_name_country_id on src.country_names(country_id);"#;
+            create index country_name_country_id on src_next.country_names(country_id);
+            create index country_name_lang_codes on src_next.country_names using gin(lang_codes);
+            create index country_name_search_key on src_next.country_names(search_key);"#;
 
     sqlx::raw_sql(sql).execute(pool)
         .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
@@ -54,24 +69,31 @@ pub async fn create_country_tables(pool: &Pool<Postgres>) -> Result<(), AppError
 }
 
 
-pub async fn import_data(data_folder: &PathBuf, source_file_name: &str, pool: &Pool<Postgres>) -> Result<(), AppError> {
+pub async fn import_data(source_file: &PathBuf, rules: &NameRules, pool: &Pool<Postgres>) -> Result<(), AppError> {
 
-    import::import_countries_data(data_folder, source_file_name, pool).await?;
+    import::import_countries_data(source_file, rules, pool).await?;
 
     transfer_countries(pool).await?;
     create_country_names(pool).await?;
     adjust_names(pool).await?;
+    add_search_keys("country_names", "alt_name", pool).await?;
 
     Ok(())
 }
 
 
+pub fn read_ids(source_file: &Path) -> Result<Vec<i64>, AppError> {
+
+    import::read_country_ids(source_file)
+}
+
+
 async fn transfer_countries(pool: &Pool<Postgres>) -> Result<(), AppError> {
 
-    let sql = r#"insert into src.countries (id, rank, iso_code, country_name,
-              continent, tld, capital)
+    let sql = r#"insert into src_next.countries (id, rank, iso_code, country_name,
+              orig_name, continent, tld, capital)
               select id, rank, iso_code, country_name,
-              continent, tld, capital
+              orig_name, continent, tld, capital
               from geo.countries
               order by country_name;"#;
 
@@ -79,6 +101,7 @@ async fn transfer_countries(pool: &Pool<Postgres>) -> Result<(), AppError> {
                 .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
 
     info!("{} country records transferred to geo schema", res.rows_affected());
+    record_count("countries transferred", res.rows_affected());
 
     Ok(())
 }
@@ -86,9 +109,11 @@ async fn transfer_countries(pool: &Pool<Postgres>) -> Result<(), AppError> {
 
 async fn create_country_names(pool: &Pool<Postgres>) -> Result<(), AppError> {
 
-    let sql = r#"insert into src.country_names (country_id, country_name, alt_name, langlist)
-        select g.id, g.country_name, a.alt_name, a.langs
-        from src.countries g
+    let sql = r#"insert into src_next.country_names (country_id, country_name, alt_name, orig_alt_name, langlist, lang_codes,
+        is_preferred, is_short, is_colloquial, is_historic, is_transliterated, yfrom, yto)
+        select g.id, g.country_name, a.alt_name, a.orig_name, a.langs, a.lang_codes,
+        a.is_preferred, a.is_short, a.is_colloquial, a.is_historic, a.is_transliterated, a.yfrom, a.yto
+        from src_next.countries g
         inner join geo.alt_names a
         on g.id = a.id;"#;
 
@@ -96,6 +121,7 @@ async fn create_country_names(pool: &Pool<Postgres>) -> Result<(), AppError> {
         .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
 
     info!("{} country name records created", res.rows_affected());
+    record_count("country names created", res.rows_affected());
 
     Ok(())
 }
@@ -106,11 +132,11 @@ async fn adjust_names(pool: &Pool<Postgres>) -> Result<(), AppError> {
     // First add a few (currently 5) country names that are the name in the
     // countries table, but which do not seem to be present in the country_name list
 
-    let sql = r#"insert into src.country_names (country_id, country_name, alt_name, langlist)
-        select c.id, c.country_name, c.country_name, ''
-        from src.countries c
+    let sql = r#"insert into src_next.country_names (country_id, country_name, alt_name, orig_alt_name, langlist)
+        select c.id, c.country_name, c.country_name, c.orig_name, ''
+        from src_next.countries c
         left join 
-            (select cn.country_name from src.country_names cn
+            (select cn.country_name from src_next.country_names cn
              where country_name = alt_name
              order by country_name) m
         on c.country_name = m.country_name
@@ -120,17 +146,18 @@ async fn adjust_names(pool: &Pool<Postgres>) -> Result<(), AppError> {
         .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
 
     info!("{} missing country name records added", res.rows_affected());
+    record_count("missing country names added", res.rows_affected());
 
     // Second remove three countries that for most purposes do not exist, from both 
     // country and country name records
 
-    let sql = r#"delete from src.countries 
+    let sql = r#"delete from src_next.countries 
               where country_name in ('Serbia and Montenegro', 'Macao', 'Hong Kong')"#;
 
     sqlx::raw_sql(sql).execute(pool)
         .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
 
-    let sql = r#"delete from src.country_names
+    let sql = r#"delete from src_next.country_names
             where country_name in ('Serbia and Montenegro', 'Macao', 'Hong Kong')"#;
 
     sqlx::raw_sql(sql).execute(pool)
@@ -42,6 +42,21 @@ pub enum AppError {
 
     #[error("Error during CSV read operation: {0:?}")]
     CsvError(#[from] csv::Error),
+
+    #[error("Error when reading zip archive: {0:?}")]
+    ZipError(#[from] zip::result::ZipError),
+
+    #[error("Stage {0} requires table {1}, which does not exist")]
+    MissingStageTable(String, String),
+
+    #[error("No previous src schema is available to roll back to")]
+    MissingPreviousSchema(),
+
+    #[error("geo.alt_names was built with city settings {0}, not the current {1}")]
+    AltNamesOutOfDate(String, String),
+
+    #[error("Error in a concurrent task: {0}")]
+    TaskError(String),
 }
 
 
@@ -80,6 +95,23 @@ pub fn report_error(e: AppError) -> () {
         AppError::IoError(e) => print_simple_error (e.to_string(), "IO ERROR"),
 
         AppError::CsvError(e) => print_simple_error (e.to_string(), "CSV ERROR"),
+
+        AppError::ZipError(e) => print_simple_error (e.to_string(), "ZIP ERROR"),
+
+        AppError::MissingStageTable(s, t) => print_error (
+                  format!("The {} stage requires the table {}, which does not exist.", s, t),
+                  "Include the stage that creates it in the run, or run that stage first.".to_string(), 
+                  "MISSING STAGE TABLE"),
+
+        AppError::MissingPreviousSchema() => print_error ("Unable to roll back the src schema.".to_string(),
+                  "No previous version (src_prev) exists.".to_string(), "ROLL BACK ERROR"),
+
+        AppError::AltNamesOutOfDate(p, c) => print_error (
+                  "The cities stage cannot use geo.alt_names, which was built for a different selection of cities.".to_string(),
+                  format!("Built with: {}. Now configured: {}. Rerun the alt_names stage as well.", p, c),
+                  "ALT NAMES OUT OF DATE"),
+
+        AppError::TaskError(d) => print_simple_error (d, "TASK ERROR"),
     }
 }
 
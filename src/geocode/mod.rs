@@ -0,0 +1,161 @@
+/***************************************************************************
+ * Reverse geocoding - finding the cities nearest to a coordinate. The cities
+ * in src.cities are loaded into an in-memory R-tree, so no use is made of
+ * PostGIS. Each location is held as a point on the unit sphere (x, y, z),
+ * as straight line distances between such points increase in step with
+ * distances over the earth's surface, and so the tree's nearest neighbour
+ * search gives the correct order, with no distortion near the poles or
+ * across the date line. Distances reported are great circle (haversine) ones.
+ ***************************************************************************/
+
+use crate::err::AppError;
+use rstar::primitives::GeomWithData;
+use rstar::RTree;
+use sqlx::{Pool, Postgres};
+use log::info;
+
+const EARTH_RADIUS_KM: f64 = 6371.0088;
+
+#[derive(Debug, Clone)]
+pub struct CityLocation {
+    pub id: i32,
+    pub name: String,
+    pub disamb_name: Option<String>,
+    pub country_code: Option<String>,
+    pub country_name: Option<String>,
+    pub lat: f64,
+    pub lng: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct NearestCity {
+    pub city: CityLocation,
+    pub distance_km: f64,
+}
+
+type CityPoint = GeomWithData<[f64; 3], CityLocation>;
+type CityRow = (i32, String, Option<String>, Option<String>, Option<String>, f64, f64);
+
+pub struct CityIndex {
+    tree: RTree<CityPoint>,
+}
+
+
+impl CityIndex {
+
+    pub async fn load(pool: &Pool<Postgres>) -> Result<CityIndex, AppError> {
+
+        let sql = r#"select id, name, disamb_name, country_code, country_name, lat, lng
+                     from src.cities
+                     where lat is not null and lng is not null"#;
+
+        let rows: Vec<CityRow> = sqlx::query_as(sql)
+            .fetch_all(pool).await
+            .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+        let cities = rows.into_iter()
+            .map(|(id, name, disamb_name, country_code, country_name, lat, lng)| CityLocation {
+                id, name, disamb_name, country_code, country_name, lat, lng,
+            })
+            .collect();
+
+        let index = CityIndex::from_cities(cities);
+        info!("City index built with {} locations", index.tree.size());
+
+        Ok(index)
+    }
+
+
+    pub fn from_cities(cities: Vec<CityLocation>) -> CityIndex {
+
+        let points = cities.into_iter()
+            .map(|c| GeomWithData::new(to_unit_vector(c.lat, c.lng), c))
+            .collect();
+
+        CityIndex {
+            tree: RTree::bulk_load(points),
+        }
+    }
+
+
+    pub fn nearest(&self, lat: f64, lng: f64, count: usize) -> Vec<NearestCity> {
+
+        self.tree.nearest_neighbor_iter(to_unit_vector(lat, lng))
+            .take(count)
+            .map(|p| NearestCity {
+                distance_km: haversine_km(lat, lng, p.data.lat, p.data.lng),
+                city: p.data.clone(),
+            })
+            .collect()
+    }
+}
+
+
+pub async fn find_nearest_cities(lat: f64, lng: f64, count: usize, pool: &Pool<Postgres>) -> Result<Vec<NearestCity>, AppError> {
+
+    // Convenience function for single look ups - when many coordinates
+    // are to be processed the CityIndex should be loaded once and reused.
+
+    let index = CityIndex::load(pool).await?;
+    Ok(index.nearest(lat, lng, count))
+}
+
+
+fn to_unit_vector(lat: f64, lng: f64) -> [f64; 3] {
+    let (lat, lng) = (lat.to_radians(), lng.to_radians());
+    [lat.cos() * lng.cos(), lat.cos() * lng.sin(), lat.sin()]
+}
+
+
+pub fn haversine_km(lat1: f64, lng1: f64, lat2: f64, lng2: f64) -> f64 {
+    let d_lat = (lat2 - lat1).to_radians();
+    let d_lng = (lng2 - lng1).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2)
+            + lat1.to_radians().cos() * lat2.to_radians().cos() * (d_lng / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_city(id: i32, name: &str, lat: f64, lng: f64) -> CityLocation {
+        CityLocation {
+            id, name: name.to_string(), disamb_name: None,
+            country_code: None, country_name: None, lat, lng,
+        }
+    }
+
+    #[test]
+    fn check_haversine_distance() {
+        let d = haversine_km(51.50853, -0.12574, 48.85341, 2.3488);  // London to Paris
+        assert!((d - 343.6).abs() < 1.0);
+        assert_eq!(haversine_km(10.0, 20.0, 10.0, 20.0), 0.0);
+    }
+
+    #[test]
+    fn check_nearest_cities_in_order() {
+        let index = CityIndex::from_cities(vec![
+            get_city(2643743, "London", 51.50853, -0.12574),
+            get_city(2988507, "Paris", 48.85341, 2.3488),
+            get_city(2950159, "Berlin", 52.52437, 13.41053),
+        ]);
+        let res = index.nearest(50.0, 1.0, 2);   // near the channel coast
+        assert_eq!(res.len(), 2);
+        assert_eq!(res[0].city.name, "Paris");
+        assert_eq!(res[1].city.name, "London");
+        assert!(res[0].distance_km < res[1].distance_km);
+    }
+
+    #[test]
+    fn check_nearest_across_date_line() {
+        let index = CityIndex::from_cities(vec![
+            get_city(1, "West", 0.0, 179.5),
+            get_city(2, "East", 0.0, -170.0),
+        ]);
+        let res = index.nearest(0.0, -179.5, 1);
+        assert_eq!(res[0].city.name, "West");
+        assert!(res[0].distance_km < 112.0);
+    }
+}
@@ -1,11 +1,11 @@
 use sqlx::{Pool, Postgres};
 use crate::AppError;
 use std::path::PathBuf;
-use std::io::BufReader;
-use std::fs::File;
-use csv::ReaderBuilder;
-use super::data_vectors::LangCodeVecs;
+use crate::setup::source_file::{file_name_of, SourceFile};
+use crate::setup::bulk_writer::{BulkWriter, CopyRow, RowWriter};
 use log::info;
+use crate::setup::run_history::record_count;
+use crate::normalise::NameRules;
 
 #[derive(serde::Deserialize)]
 struct LangCodeLine {
@@ -26,22 +26,24 @@ pub struct LangCodeRec {
     pub c2: String,
     pub c1: String,
     pub name: String,
+    pub orig_name: String,
+}
+
+impl CopyRow for LangCodeRec {
+    fn write_row(&self, row: &mut RowWriter) {
+        row.add(&self.c3).add(&self.c2).add(&self.c1).add(&self.name).add(&self.orig_name);
+    }
 }
 
 
-pub async fn import_lang_code_data(data_folder: &PathBuf, source_file_name: &str, pool: &Pool<Postgres>) -> Result<(), AppError> {
+pub async fn import_lang_code_data(source_file: &PathBuf, rules: &NameRules, pool: &Pool<Postgres>) -> Result<(), AppError> {
 
-    let source_file_path: PathBuf = [data_folder, &PathBuf::from(source_file_name)].iter().collect();
-    let file = File::open(&source_file_path)
-                .map_err(|e| AppError::IoWriteErrorWithPath(e, source_file_path))?;
-    let buf_reader = BufReader::new(file);
-    let mut csv_rdr = ReaderBuilder::new()
-        .delimiter(9)
-        .from_reader(buf_reader);
+    let source_file_name = &file_name_of(source_file);
+    let mut source = SourceFile::open_source(source_file)?;
+    let mut csv_rdr = source.csv_reader(None, true)?;
 
         let mut i = 0;
-        let vector_size = 2500;
-        let mut dv: LangCodeVecs = LangCodeVecs::new(vector_size);
+        let mut bw = BulkWriter::new(pool, "geo.lang_codes", &["c3", "c2", "c1", "name", "orig_name"]);
              
         for result in csv_rdr.deserialize() {
      
@@ -51,16 +53,19 @@ pub async fn import_lang_code_data(data_folder: &PathBuf, source_file_name: &str
                 c3: source.c3,
                 c2: source.c2,
                 c1: source.c1,
-                name: source.name.trim().replace(".", "").replace("'", "’"),
+                name: rules.normalise(&source.name),
+                orig_name: source.name,
             };
 
-            dv.add_data(&lang_code_rec);   // transfer data to vectors
+            bw.add(&lang_code_rec).await?;
             i +=1;    
 
          }
               
-         dv.store_data(&pool).await?;
+         csv_rdr.get_ref().log_skipped_lines(source_file_name);
+         bw.finish().await?;
          info!("{} records processed from {} to geo.lang_codes", i, source_file_name);
+         record_count("language code source records", i);
                  
          Ok(())
 }
@@ -1,23 +1,14 @@
-mod data_vectors;
 mod import;
 
 use sqlx::{Pool, Postgres};
 use crate::AppError;
 use std::path::PathBuf;
 use log::info;
+use crate::setup::run_history::record_count;
+use crate::normalise::NameRules;
 
 pub async fn create_lang_code_tables(pool: &Pool<Postgres>) -> Result<(), AppError> {
 
-    // initial call to DB, below, ensures schemas in place, reduces spurious warnings
-
-    let sql = r#"SET client_min_messages TO WARNING;  
-    create schema if not exists src;
-    create schema if not exists geo;
-    create schema if not exists mdr;"#;
-
-    sqlx::raw_sql(sql).execute(pool)
-        .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
-
     let sql = r#"drop table if exists geo.lang_codes;
                 create table geo.lang_codes
                 (
@@ -25,16 +16,18 @@ pub async fn create_lang_code_tables(pool: &Pool<Postgres>) -> Result<(), AppErr
                   , c2           varchar
                   , c1           varchar
                   , name         varchar
+                  , orig_name    varchar
                 );"#;
 
     sqlx::raw_sql(sql).execute(pool)
         .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
 
-    let sql = r#"drop table if exists src.lang_codes;
-                create table src.lang_codes
+    let sql = r#"drop table if exists src_next.lang_codes;
+                create table src_next.lang_codes
                 (
                     code         varchar primary key
                   , name         varchar  
+                  , orig_name    varchar
                   , code_type    varchar
                 );"#;
 
@@ -45,30 +38,31 @@ pub async fn create_lang_code_tables(pool: &Pool<Postgres>) -> Result<(), AppErr
 }
 
 
-pub async fn import_data(data_folder: &PathBuf, source_file_name: &str, pool: &Pool<Postgres>) -> Result<(), AppError> {
+pub async fn import_data(source_file: &PathBuf, rules: &NameRules, pool: &Pool<Postgres>) -> Result<(), AppError> {
 
-    import::import_lang_code_data(data_folder, source_file_name, pool).await?;
+    import::import_lang_code_data(source_file, rules, pool).await?;
     transfer_data(pool).await?;
     delete_src_table(pool).await
 }
 
 pub async fn transfer_data(pool: &Pool<Postgres>) -> Result<(), AppError> {
 
-    let sql = r#"insert into src.lang_codes (code, name, code_type)
+    let sql = r#"insert into src_next.lang_codes (code, name, orig_name, code_type)
                 select * from
-                (select c2 as code, name, '639-2' as code_type
+                (select c2 as code, name, orig_name, '639-2' as code_type
                 from geo.lang_codes where c3 = '' and c2 not in ('frr', 'srn', 'syc', 'rup'))
                 union
-                (select c3 as code, name, '639-3' as code_type
+                (select c3 as code, name, orig_name, '639-3' as code_type
                 from geo.lang_codes where c3 <> '')
                 union
-                (select c1 as code, name, '639-1' as code_type
+                (select c1 as code, name, orig_name, '639-1' as code_type
                 from geo.lang_codes where c1 <> '')
                 order by code"#;
 
     let res = sqlx::raw_sql(sql).execute(pool)
         .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
-    info!("{} rows created in src.lang_codes", res.rows_affected());
+    info!("{} rows created in src_next.lang_codes", res.rows_affected());
+    record_count("language codes created", res.rows_affected());
 
     Ok(())
 }
@@ -1,6 +1,8 @@
 
 pub mod setup;
 pub mod err;
+pub mod geocode;
+pub mod normalise;
 mod lang_codes;
 mod alt_names;
 mod cities;
@@ -9,16 +11,22 @@ mod admins;
 mod scopes;
 
 use setup::cli_reader;
+use setup::stages::{self, Stage};
+use setup::{staging, run_history, source_file, InitParams};
 use err::AppError;
+use sqlx::{Pool, Postgres};
+use log::info;
 use std::ffi::OsString;
 use std::path::PathBuf;
 use std::fs;
+use std::collections::HashSet;
 
 pub async fn run(args: Vec<OsString>) -> Result<(), AppError> {
 
     let cli_pars: cli_reader::CliPars;
     cli_pars = cli_reader::fetch_valid_arguments(args)?;
     let flags = cli_pars.flags;
+    let nearest = cli_pars.nearest;
 
     let config_file = PathBuf::from("./app_config.toml");
     let config_string: String = fs::read_to_string(&config_file)
@@ -26,54 +34,153 @@ pub async fn run(args: Vec<OsString>) -> Result<(), AppError> {
                               
     let params = setup::get_params(cli_pars, &config_string)?;
     setup::establish_log(&params)?;
-    let pool = setup::get_db_pool().await?;
+
+    // The alt name writer tasks each need a connection, with one more
+    // left for the rest of the import.
+
+    let pool = setup::get_db_pool(5.max(params.names.writer_tasks as u32 + 1)).await?;
          
+    if let Some(np) = nearest
+    {
+        let index = geocode::CityIndex::load(&pool).await?;
+        for n in index.nearest(np.lat, np.lng, np.count) {
+            let c = &n.city;
+            println!("{:>8.1} km  {} ({}), {} [{}]", n.distance_km, c.name, 
+                c.disamb_name.as_deref().unwrap_or(""), c.country_name.as_deref().unwrap_or(""), c.id);
+        }
+    }
+
+    if flags.rollback
+    {
+        staging::roll_back_to_previous(&pool).await?;
+    }
+
     if flags.import_data   
     {
-        // The latin_only parameter makes the process include Latin alternative names only
-        // By default it is true, but is switchable to false using the -n command flag.
+        // Check that every selected stage will find the tables it needs
+        // before starting any of them.
+
+        setup::create_schemas(&pool).await?;
+        run_history::create_history_tables(&pool).await?;
+        stages::check_prerequisites(&params.stages, &pool).await?;
+        run_history::check_kept_ids_basis(&params, &pool).await?;
+
+        // The run is recorded in imp_geo.runs whether or not it succeeds.
+
+        let run_id = run_history::start_run(&params, &pool).await?;
+        match import_stages(&params, run_id, &pool).await {
+            Ok(_) => run_history::end_run(run_id, "completed", &pool).await?,
+            Err(e) => {
+                run_history::end_run(run_id, "failed", &pool).await?;
+                return Err(e);
+            },
+        }
+     }
+
+     Ok(())  
+}
 
-        let latin_only = !flags.include_nonlatin;
 
-        // Do language codes - import first, as required by alt name processing below
+async fn import_stages(params: &InitParams, run_id: i32, pool: &Pool<Postgres>) -> Result<(), AppError> {
 
-        lang_codes::create_lang_code_tables(&pool).await?;
-        let file_name = "iso-languagecodes.txt";
-        lang_codes::import_data(&params.data_folder, file_name, &pool).await?;
+    // Alternative names are retained only if written in the scripts listed in
+    // the config file (by default Latin only). The -n command flag retains all names.
 
-        // Do Alt Names - import second, as this data is needed by later imports
+    let scripts = match params.flags.include_nonlatin {
+        true => None,
+        false => Some(params.names.scripts.as_slice()),
+    };
+    let files = &params.files;
 
-        alt_names::create_alt_name_table(&pool).await?;
-        let file_name = "alternateNamesV2.txt";
-        alt_names::import_data(&params.data_folder, file_name, &pool, latin_only).await?;
+    // All src tables are built in src_next, which replaces src only
+    // once every selected stage has completed successfully.
 
-        // Admins 1 and 2 data.
+    staging::prepare_staging_schema(&params.stages, pool).await?;
 
-        admins::create_admins_tables(&pool).await?;
-        let file_name = "admin1CodesASCII.txt";
-        admins::import_data(&params.data_folder, file_name, &pool).await?;
-        let file_name = "admin2Codes.txt";
-        admins::import_data(&params.data_folder, file_name, &pool).await?;
+    for stage in params.stages.iter() {
 
-        // Countries data.
+        info!("Starting stage: {}", stage.name());
+        run_history::start_stage(run_id, *stage, pool).await?;
 
-        countries::create_country_tables(&pool).await?;
-        let file_name = "countryInfo.txt";
-        countries::import_data(&params.data_folder, file_name, &pool).await?;
+        for file_path in files.for_stage(*stage) {
+            let source_path = source_file::resolve_source_path(file_path)?;
+            run_history::record_source_file(run_id, *stage, &source_path, pool).await?;
+        }
 
-        // Cities data.
+        match stage {
 
-        cities::create_city_tables(&pool).await?;
-        let file_name = "cities5000.txt";
-        cities::import_data(&params.data_folder, file_name, &pool).await?;
+            // Language codes - required by alt name processing below
 
-        // Scope data.
+            Stage::LangCodes => {
+                lang_codes::create_lang_code_tables(pool).await?;
+                lang_codes::import_data(&files.lang_codes, &params.normalisation.names, pool).await?;
+            },
 
-        scopes::create_scope_tables(&pool).await?;
-        let file_name = "no-country.txt";
-        scopes::import_data(&params.data_folder, file_name, &pool).await?;
+            // Alt Names - needed by the country and city imports
 
-     }
+            Stage::AltNames => {
+                let kept_ids = read_kept_ids(params)?;
+                alt_names::create_alt_name_table(pool).await?;
+                alt_names::import_data(&files.alt_names, pool, scripts, params.flags.transliterate,
+                                       &params.normalisation.names, kept_ids, params.names.writer_tasks).await?;
+                run_history::record_kept_ids_basis(run_id, params, pool).await?;
+            },
 
-     Ok(())  
+            // Countries data.
+
+            Stage::Countries => {
+                countries::create_country_tables(pool).await?;
+                countries::import_data(&files.countries, &params.normalisation.names, pool).await?;
+            },
+
+            // Admins 1 and 2 data - after countries, to which they are linked.
+
+            Stage::Admins => {
+                admins::create_admins_tables(pool).await?;
+                admins::import_data(&files.admin1_codes, "adm1s", &params.normalisation.names, pool).await?;
+                admins::import_data(&files.admin2_codes, "adm2s", &params.normalisation.names, pool).await?;
+                admins::transfer_data(pool).await?;
+            },
+
+            // Cities data.
+
+            Stage::Cities => {
+                cities::create_city_tables(pool).await?;
+                cities::import_data(&files.cities, &params.cities, &params.normalisation.cities, pool).await?;
+            },
+
+            // Scope data.
+
+            Stage::Scopes => {
+                scopes::create_scope_tables(pool).await?;
+                scopes::import_data(&files.no_country, &params.normalisation.regions, pool).await?;
+            },
+        }
+
+        run_history::end_stage(run_id, *stage, pool).await?;
+    }
+
+    staging::publish_staging_schema(pool).await
+}
+
+
+fn read_kept_ids(params: &InitParams) -> Result<HashSet<i64>, AppError> {
+
+    // The geonames ids of every country, admin area, city and region that the
+    // later stages will import. Alternative names are only needed for these,
+    // and not for the other 12 million or so features in the geonames data.
+
+    let files = &params.files;
+    let mut ids: HashSet<i64> = HashSet::new();
+
+    ids.extend(countries::read_ids(&files.countries)?);
+    ids.extend(admins::read_ids(&files.admin1_codes)?);
+    ids.extend(admins::read_ids(&files.admin2_codes)?);
+    ids.extend(cities::read_ids(&files.cities, &params.cities)?);
+    ids.extend(scopes::read_ids(&files.no_country)?);
+
+    info!("{} geonames ids collected, for which alternative names will be retained", ids.len());
+    run_history::record_count("geoname ids retained", ids.len() as u64);
+
+    Ok(ids)
 }
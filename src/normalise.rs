@@ -0,0 +1,291 @@
+/***************************************************************************
+ * Normalisation of the names read from the Geonames files. Configured rules
+ * are applied by every importer, so that a city's name, for example, can
+ * be matched against the alternate names of the same city. The rules are
+ * read from the [normalisation] section of the config file. If that is
+ * absent, each table keeps its original treatment - names are trimmed, full
+ * stops removed and straight apostrophes replaced by typographic ones, 
+ * except that city names only have their apostrophes replaced, and region 
+ * names are left unchanged. The names as supplied are kept alongside the
+ * normalised versions (in orig_name columns) so the effect can be checked.
+ * Search keys are a much more aggressive normalisation, used only for
+ * matching: the names tables each carry an indexed search_key column, and
+ * clients should use search_key() on their input to produce the same keys.
+ ***************************************************************************/
+
+use crate::err::AppError;
+use crate::setup::bulk_writer::{BulkWriter, CopyRow, RowWriter};
+use crate::setup::run_history::record_count;
+use sqlx::{Pool, Postgres};
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+use log::info;
+
+pub const DEFAULT_REPLACEMENTS: [(&str, &str); 2] = [(".", ""), ("'", "’")];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameRules {
+    pub trim: bool,
+    pub replacements: Vec<(String, String)>,
+}
+
+impl Default for NameRules {
+    fn default() -> Self {
+        NameRules {
+            trim: true,
+            replacements: DEFAULT_REPLACEMENTS.iter()
+                          .map(|(f, t)| (f.to_string(), t.to_string())).collect(),
+        }
+    }
+}
+
+impl NameRules {
+
+    pub fn apostrophes_only() -> Self {
+        NameRules {
+            trim: false,
+            replacements: vec![("'".to_string(), "’".to_string())],
+        }
+    }
+
+    pub fn unchanged() -> Self {
+        NameRules { trim: false, replacements: Vec::new() }
+    }
+
+    // Replacements are made in the order listed, after any trimming.
+
+    pub fn normalise(&self, name: &str) -> String {
+        let mut res = match self.trim {
+            true => name.trim().to_string(),
+            false => name.to_string(),
+        };
+        for (from, to) in self.replacements.iter() {
+            if res.contains(from.as_str()) {
+                res = res.replace(from.as_str(), to);
+            }
+        }
+        res
+    }
+
+    pub fn describe(&self) -> String {
+        let reps: Vec<String> = self.replacements.iter()
+                    .map(|(f, t)| format!("'{}' -> '{}'", f, t)).collect();
+        format!("trim: {}, replacements: [{}]", self.trim, reps.join(", "))
+    }
+}
+
+
+// The rules for each group of tables. Configured rules apply to all of them,
+// but by default city and region names are treated as they were originally.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NormalisationRules {
+    pub names: NameRules,    // language, country, admin area and alternate names
+    pub cities: NameRules,
+    pub regions: NameRules,
+}
+
+impl Default for NormalisationRules {
+    fn default() -> Self {
+        NormalisationRules {
+            names: NameRules::default(),
+            cities: NameRules::apostrophes_only(),
+            regions: NameRules::unchanged(),
+        }
+    }
+}
+
+impl NormalisationRules {
+
+    pub fn all(rules: NameRules) -> Self {
+        NormalisationRules {
+            names: rules.clone(),
+            cities: rules.clone(),
+            regions: rules,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        if self.names == self.cities && self.names == self.regions {
+            return self.names.describe();
+        }
+        format!("names - {}; cities - {}; regions - {}", self.names.describe(), 
+                self.cities.describe(), self.regions.describe())
+    }
+}
+
+
+// Search keys are built from the compatibility decomposition (NFKD) of the
+// name, with the combining marks (accents) dropped and letters lower cased.
+// Full stops and apostrophes are removed, so 'D.C.' gives 'dc' and 'L’Aquila'
+// 'laquila', and any other run of spaces or punctuation becomes one space.
+
+const KEY_DROPPED: [char; 5] = ['.', '\'', '’', '‘', 'ʼ'];
+
+pub fn search_key(name: &str) -> String {
+
+    let mut key = String::with_capacity(name.len());
+    let mut gap = false;
+    for c in name.nfkd().filter(|c| !is_combining_mark(*c) && !KEY_DROPPED.contains(c)) {
+        if c.is_alphanumeric() {
+            if gap && !key.is_empty() {
+                key.push(' ');
+            }
+            gap = false;
+            match folded_letter(c) {
+                Some(s) => key.push_str(s),
+                None => key.extend(c.to_lowercase()),
+            }
+        }
+        else {
+            gap = true;
+        }
+    }
+    key
+}
+
+
+// Letters that have no decomposition, but which are usually 
+// typed without their stroke or as two letters. The sharp s and 
+// final sigma are folded as they would be by full case folding.
+
+fn folded_letter(c: char) -> Option<&'static str> {
+    match c {
+        'ß' | 'ẞ' => Some("ss"),
+        'æ' | 'Æ' => Some("ae"),
+        'œ' | 'Œ' => Some("oe"),
+        'þ' | 'Þ' => Some("th"),
+        'ø' | 'Ø' => Some("o"),
+        'ł' | 'Ł' => Some("l"),
+        'đ' | 'Đ' => Some("d"),
+        'ħ' | 'Ħ' => Some("h"),
+        'ı' => Some("i"),
+        'ς' => Some("σ"),
+        _ => None,
+    }
+}
+
+
+struct KeyRec {
+    name: String,
+    search_key: String,
+}
+
+impl CopyRow for KeyRec {
+    fn write_row(&self, row: &mut RowWriter) {
+        row.add(&self.name).add(&self.search_key);
+    }
+}
+
+
+pub async fn add_search_keys(table: &str, name_column: &str, pool: &Pool<Postgres>) -> Result<(), AppError> {
+
+    // Keys are generated here, rather than in SQL, so that they are identical
+    // to those produced by search_key() in client code. Each distinct name 
+    // is keyed once, with the keys then joined back to the named table.
+
+    let sql = format!(r#"select distinct {1} from src_next.{0} where {1} is not null"#, table, name_column);
+    let names: Vec<String> = sqlx::query_scalar(&sql)
+        .fetch_all(pool).await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    let sql = format!(r#"drop table if exists geo.{0}_keys;
+                create table geo.{0}_keys
+                (
+                      name          varchar primary key
+                    , search_key    varchar
+                );"#, table);
+
+    sqlx::raw_sql(&sql).execute(pool)
+        .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    let mut bw = BulkWriter::new(pool, &format!("geo.{}_keys", table), &["name", "search_key"]);
+    for name in names {
+        let search_key = search_key(&name);
+        bw.add(&KeyRec { name, search_key }).await?;
+    }
+    bw.finish().await?;
+
+    let sql = format!(r#"update src_next.{0} t
+                set search_key = k.search_key
+                from geo.{0}_keys k
+                where t.{1} = k.name;
+                
+                drop table geo.{0}_keys;"#, table, name_column);
+
+    let res = sqlx::raw_sql(&sql).execute(pool)
+        .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    info!("{} search keys added to src_next.{}", res.rows_affected(), table);
+    record_count(&format!("{} search keys added", table), res.rows_affected());
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_default_rules_trim_and_replace() {
+        let rules = NameRules::default();
+        assert_eq!(rules.normalise(" St. Louis "), "St Louis");
+        assert_eq!(rules.normalise("Washington, D.C."), "Washington, DC");
+        assert_eq!(rules.normalise("L'Aquila"), "L’Aquila");
+    }
+
+    #[test]
+    fn check_default_table_rules_match_previous_behaviour() {
+        let rules = NormalisationRules::default();
+        assert_eq!(rules.names.normalise(" St. Louis "), "St Louis");
+        assert_eq!(rules.cities.normalise("St. Louis"), "St. Louis");
+        assert_eq!(rules.cities.normalise("L'Aquila"), "L’Aquila");
+        assert_eq!(rules.regions.normalise("St. Helena's "), "St. Helena's ");
+    }
+
+    #[test]
+    fn check_configured_rules_applied_in_order() {
+        let rules = NameRules {
+            trim: false,
+            replacements: vec![("'".to_string(), "’".to_string()), ("’’".to_string(), "’".to_string())],
+        };
+        assert_eq!(rules.normalise(" St. Louis"), " St. Louis");
+        assert_eq!(rules.normalise("O''Brien"), "O’Brien");
+    }
+
+    #[test]
+    fn check_empty_rules_leave_names_unchanged() {
+        let rules = NameRules { trim: false, replacements: Vec::new() };
+        assert_eq!(rules.normalise(" Washington, D.C. "), " Washington, D.C. ");
+    }
+
+
+    #[test]
+    fn check_search_keys_fold_accents_and_case() {
+        assert_eq!(search_key("Saint-Étienne"), "saint etienne");
+        assert_eq!(search_key("  SÃO   Paulo "), "sao paulo");
+        assert_eq!(search_key("Düsseldorf"), "dusseldorf");
+        assert_eq!(search_key("Straße"), "strasse");
+        assert_eq!(search_key("Łódź"), "lodz");
+        assert_eq!(search_key("Tromsø"), "tromso");
+    }
+
+    #[test]
+    fn check_search_keys_collapse_punctuation() {
+        assert_eq!(search_key("Washington, D.C."), search_key("Washington DC"));
+        assert_eq!(search_key("L’Aquila"), "laquila");
+        assert_eq!(search_key("L'Aquila"), "laquila");
+        assert_eq!(search_key("Stratford-upon-Avon"), "stratford upon avon");
+        assert_eq!(search_key("ﬁnale (Ligure)"), "finale ligure");
+        assert_eq!(search_key(" - "), "");
+    }
+
+    #[test]
+    fn check_search_keys_keep_non_latin_letters() {
+        assert_eq!(search_key("Αθήνα"), "αθηνα");
+        assert_eq!(search_key("ΣΠΆΡΤΗ"), search_key("σπάρτη"));
+        assert_eq!(search_key("Москва"), "москва");
+        assert_eq!(search_key("東京"), "東京");
+    }
+}
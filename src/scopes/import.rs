@@ -1,11 +1,11 @@
 use sqlx::{Pool, Postgres};
 use crate::AppError;
-use std::path::PathBuf;
-use std::io::BufReader;
-use std::fs::File;
-use csv::ReaderBuilder;
-use super::data_vectors::ScopeVecs;
+use std::path::{Path, PathBuf};
+use crate::setup::source_file::{file_name_of, SourceFile};
+use crate::setup::bulk_writer::{BulkWriter, CopyRow, RowWriter};
 use log::info;
+use crate::setup::run_history::record_count;
+use crate::normalise::NameRules;
 
 #[derive(serde::Deserialize)]
 #[allow(dead_code)]
@@ -35,56 +35,72 @@ pub struct ScopeRec {
     pub id: i64,
     pub feature_code: String,
     pub name: String,
+    pub orig_name: String,
     pub members: Option<String>,
 }
 
-pub async fn import_scope_data(data_folder: &PathBuf, source_file_name: &str, pool: &Pool<Postgres>) -> Result<(), AppError> {
+impl CopyRow for ScopeRec {
+    fn write_row(&self, row: &mut RowWriter) {
+        row.add(&self.id).add(&self.feature_code).add(&self.name).add(&self.orig_name).add(&self.members);
+    }
+}
+
+pub async fn import_scope_data(source_file: &PathBuf, rules: &NameRules, pool: &Pool<Postgres>) -> Result<(), AppError> {
 
-    let source_file_path: PathBuf = [data_folder, &PathBuf::from(source_file_name)].iter().collect();
-    let file = File::open(source_file_path)?;
-    let buf_reader = BufReader::new(file);
-    let mut csv_rdr = ReaderBuilder::new()
-        .has_headers(false)
-        .delimiter(9)
-        .from_reader(buf_reader);
+    let source_file_name = &file_name_of(source_file);
+    let mut source = SourceFile::open_source(source_file)?;
+    let mut csv_rdr = source.csv_reader(Some("geonameid"), false)?;
     
     let mut i = 0;
-    let vector_size = 500;
-    let mut dv: ScopeVecs = ScopeVecs::new(vector_size);
+    let mut bw = BulkWriter::new(pool, "src_next.regions", &["id", "feature_code", "name", "orig_name", "members"]);
 
-    let rgn = "RGN".to_string();
-    let cont = "CONT".to_string();
-            
     for result in csv_rdr.deserialize() {
     
         let source: ScopeLine = result?;
 
-        let mut create_rec = false;
-        match source.feature_code.clone() {
-            Some(s) => {
-                if s == rgn || s == cont {
-                    create_rec = true;
-                }
-            },
-            None => {},
-        };
-
-        if create_rec {
+        if is_included_scope(&source) {
             let scope_rec = ScopeRec {
                 id: source.geonameid,
                 feature_code: source.feature_code.unwrap(),
-                name: source.name,
+                name: rules.normalise(&source.name),
+                orig_name: source.name,
                 members: source.cc2,
             };
 
-            dv.add_data(&scope_rec);   // transfer data to vectors
+            bw.add(&scope_rec).await?;
             i +=1;    
 
         }
     }
             
-    dv.store_data(&pool).await?;
-    info!("{} records processed from {} to src.regions", i, source_file_name);
+    csv_rdr.get_ref().log_skipped_lines(source_file_name);
+    bw.finish().await?;
+    info!("{} records processed from {} to src_next.regions", i, source_file_name);
+    record_count("region source records", i);
 
     Ok(())
 }
+
+
+pub fn read_scope_ids(source_file: &Path) -> Result<Vec<i64>, AppError> {
+
+    let mut source = SourceFile::open_source(source_file)?;
+    let mut csv_rdr = source.csv_reader(Some("geonameid"), false)?;
+
+    let mut ids = Vec::new();
+    for result in csv_rdr.deserialize() {
+        let source: ScopeLine = result?;
+        if is_included_scope(&source) {
+            ids.push(source.geonameid);
+        }
+    }
+    Ok(ids)
+}
+
+
+fn is_included_scope(source: &ScopeLine) -> bool {
+
+    // Only regions and continents are imported.
+
+    matches!(source.feature_code.as_deref(), Some("RGN") | Some("CONT"))
+}
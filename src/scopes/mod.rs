@@ -1,20 +1,23 @@
-mod data_vectors;
 mod import;
 
 use sqlx::{Pool, Postgres};
 use crate::AppError;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use crate::normalise::{add_search_keys, NameRules};
 
 pub async fn create_scope_tables(pool: &Pool<Postgres>) -> Result<(), AppError> {
 
-    let sql = r#"drop table if exists src.regions;
-            create table src.regions
+    let sql = r#"drop table if exists src_next.regions;
+            create table src_next.regions
             (
                   id               int
                 , feature_code     varchar
                 , name             varchar
+                , orig_name        varchar
                 , members          varchar
-            );"#;
+                , search_key       varchar
+            );
+            create index regions_search_key on src_next.regions(search_key);"#;
 
     sqlx::raw_sql(sql).execute(pool)
             .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
@@ -23,9 +26,10 @@ pub async fn create_scope_tables(pool: &Pool<Postgres>) -> Result<(), AppError>
 }
 
 
-pub async fn import_data(data_folder: &PathBuf, source_file_name: &str, pool: &Pool<Postgres>) -> Result<(), AppError> {
+pub async fn import_data(source_file: &PathBuf, rules: &NameRules, pool: &Pool<Postgres>) -> Result<(), AppError> {
 
-    import::import_scope_data(data_folder, source_file_name, pool).await?;
+    import::import_scope_data(source_file, rules, pool).await?;
+    add_search_keys("regions", "name", pool).await?;
     let sql = r#"SET client_min_messages TO NOTICE;"#;   // final command to DB
 
     sqlx::raw_sql(sql).execute(pool)
@@ -34,3 +38,8 @@ pub async fn import_data(data_folder: &PathBuf, source_file_name: &str, pool: &P
     
 }
 
+
+pub fn read_ids(source_file: &Path) -> Result<Vec<i64>, AppError> {
+
+    import::read_scope_ids(source_file)
+}
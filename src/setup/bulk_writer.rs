@@ -0,0 +1,277 @@
+/***************************************************************************
+ * Bulk loading of records into the database, using Postgres COPY FROM STDIN
+ * (text format). Each record type implements CopyRow, writing its fields in
+ * the order of the columns given to the BulkWriter. Rows are escaped into a
+ * single buffer, which is sent as one COPY whenever it reaches FLUSH_BYTES,
+ * and again when the writer is finished. This avoids building a vector per
+ * column, and cloning each value into it, as the UNNEST inserts required.
+ * The buffering (BatchBuilder) is kept apart from the writing (write_batch),
+ * so that batches can be built on one thread and written by other tasks.
+ ***************************************************************************/
+
+use crate::err::AppError;
+use sqlx::postgres::PgPoolCopyExt;
+use sqlx::{Pool, Postgres};
+
+const FLUSH_BYTES: usize = 4 * 1024 * 1024;
+
+
+pub trait CopyRow {
+    fn write_row(&self, row: &mut RowWriter);
+}
+
+
+pub trait CopyField {
+    fn write_field(&self, buf: &mut Vec<u8>);
+}
+
+
+pub struct RowWriter<'a> {
+    buf: &'a mut Vec<u8>,
+    first: bool,
+}
+
+
+impl RowWriter<'_> {
+
+    pub fn add(&mut self, field: &dyn CopyField) -> &mut Self {
+        if !self.first {
+            self.buf.push(b'\t');
+        }
+        self.first = false;
+        field.write_field(self.buf);
+        self
+    }
+}
+
+
+// A set of rows for one table, ready to be sent as a single COPY.
+
+pub struct CopyBatch {
+    sql: String,
+    data: Vec<u8>,
+}
+
+
+pub struct BatchBuilder {
+    sql: String,
+    buffer: Vec<u8>,
+    rows: u64,
+}
+
+
+impl BatchBuilder {
+
+    pub fn new(table: &str, columns: &[&str]) -> Self {
+        BatchBuilder {
+            sql: format!("COPY {} ({}) FROM STDIN", table, columns.join(", ")),
+            buffer: Vec::with_capacity(FLUSH_BYTES + 64 * 1024),
+            rows: 0,
+        }
+    }
+
+
+    pub fn add<R: CopyRow>(&mut self, rec: &R) -> Option<CopyBatch> {
+
+        // Returns a batch once the buffer is full.
+
+        let mut row = RowWriter { buf: &mut self.buffer, first: true };
+        rec.write_row(&mut row);
+        self.buffer.push(b'\n');
+        self.rows += 1;
+
+        match self.buffer.len() >= FLUSH_BYTES {
+            true => self.take(),
+            false => None,
+        }
+    }
+
+
+    pub fn take(&mut self) -> Option<CopyBatch> {
+
+        // Returns any rows not yet sent as a batch.
+
+        if self.rows == 0 {
+            return None;
+        }
+        let data = std::mem::replace(&mut self.buffer, Vec::with_capacity(FLUSH_BYTES + 64 * 1024));
+        self.rows = 0;
+        Some(CopyBatch { sql: self.sql.clone(), data })
+    }
+}
+
+
+pub async fn write_batch(pool: &Pool<Postgres>, batch: &CopyBatch) -> Result<u64, AppError> {
+
+    let mut copy = pool.copy_in_raw(&batch.sql).await
+        .map_err(|e| AppError::SqlxError(e, batch.sql.clone()))?;
+
+    if let Err(e) = copy.send(batch.data.as_slice()).await {
+        let _ = copy.abort("bulk write failed").await;
+        return Err(AppError::SqlxError(e, batch.sql.clone()));
+    }
+
+    copy.finish().await
+        .map_err(|e| AppError::SqlxError(e, batch.sql.clone()))
+}
+
+
+// Builds and writes batches in turn, for use when the records
+// are read and written within a single task.
+
+pub struct BulkWriter {
+    pool: Pool<Postgres>,
+    builder: BatchBuilder,
+    total: u64,
+}
+
+
+impl BulkWriter {
+
+    pub fn new(pool: &Pool<Postgres>, table: &str, columns: &[&str]) -> Self {
+        BulkWriter {
+            pool: pool.clone(),
+            builder: BatchBuilder::new(table, columns),
+            total: 0,
+        }
+    }
+
+
+    pub async fn add<R: CopyRow>(&mut self, rec: &R) -> Result<(), AppError> {
+        if let Some(batch) = self.builder.add(rec) {
+            self.total += write_batch(&self.pool, &batch).await?;
+        }
+        Ok(())
+    }
+
+
+    pub async fn finish(mut self) -> Result<u64, AppError> {
+
+        // Returns the total number of rows written.
+
+        if let Some(batch) = self.builder.take() {
+            self.total += write_batch(&self.pool, &batch).await?;
+        }
+        Ok(self.total)
+    }
+}
+
+
+// Field encodings for the COPY text format. Backslashes and the
+// tab, newline and carriage return characters must be escaped,
+// and nulls are written as \N.
+
+impl CopyField for str {
+    fn write_field(&self, buf: &mut Vec<u8>) {
+        for b in self.bytes() {
+            match b {
+                b'\\' => buf.extend_from_slice(b"\\\\"),
+                b'\t' => buf.extend_from_slice(b"\\t"),
+                b'\n' => buf.extend_from_slice(b"\\n"),
+                b'\r' => buf.extend_from_slice(b"\\r"),
+                _ => buf.push(b),
+            }
+        }
+    }
+}
+
+impl CopyField for String {
+    fn write_field(&self, buf: &mut Vec<u8>) {
+        self.as_str().write_field(buf)
+    }
+}
+
+impl CopyField for bool {
+    fn write_field(&self, buf: &mut Vec<u8>) {
+        buf.push(if *self { b't' } else { b'f' });
+    }
+}
+
+impl CopyField for i32 {
+    fn write_field(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self.to_string().as_bytes());
+    }
+}
+
+impl CopyField for i64 {
+    fn write_field(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self.to_string().as_bytes());
+    }
+}
+
+impl CopyField for f64 {
+    fn write_field(&self, buf: &mut Vec<u8>) {
+        let s = match *self {
+            f if f == f64::INFINITY => "Infinity".to_string(),
+            f if f == f64::NEG_INFINITY => "-Infinity".to_string(),
+            f => f.to_string(),   // includes NaN, which Postgres accepts
+        };
+        buf.extend_from_slice(s.as_bytes());
+    }
+}
+
+impl<T: CopyField + ?Sized> CopyField for &T {
+    fn write_field(&self, buf: &mut Vec<u8>) {
+        (**self).write_field(buf)
+    }
+}
+
+impl<T: CopyField> CopyField for Option<T> {
+    fn write_field(&self, buf: &mut Vec<u8>) {
+        match self {
+            Some(v) => v.write_field(buf),
+            None => buf.extend_from_slice(b"\\N"),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestRec {
+        id: i64,
+        name: String,
+        code: Option<String>,
+        flag: bool,
+        lat: Option<f64>,
+    }
+
+    impl CopyRow for TestRec {
+        fn write_row(&self, row: &mut RowWriter) {
+            row.add(&self.id).add(&self.name).add(&self.code).add(&self.flag).add(&self.lat);
+        }
+    }
+
+    fn encode(rec: &TestRec) -> String {
+        let mut buf = Vec::new();
+        rec.write_row(&mut RowWriter { buf: &mut buf, first: true });
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn check_row_fields_tab_separated() {
+        let rec = TestRec { id: 2643743, name: "London".to_string(), code: None, flag: true, lat: Some(51.50853) };
+        assert_eq!(encode(&rec), "2643743\tLondon\t\\N\tt\t51.50853");
+    }
+
+    #[test]
+    fn check_batch_holds_all_rows() {
+        let mut builder = BatchBuilder::new("geo.test", &["id", "name", "code", "flag", "lat"]);
+        for id in 1..=3 {
+            let rec = TestRec { id, name: "X".to_string(), code: None, flag: false, lat: None };
+            assert!(builder.add(&rec).is_none());
+        }
+        let batch = builder.take().unwrap();
+        assert_eq!(batch.sql, "COPY geo.test (id, name, code, flag, lat) FROM STDIN");
+        assert_eq!(batch.data.iter().filter(|b| **b == b'\n').count(), 3);
+        assert!(builder.take().is_none());
+    }
+
+    #[test]
+    fn check_special_characters_escaped() {
+        let rec = TestRec { id: 1, name: "a\tb\\c\nd\re".to_string(), code: Some("Ébreuil".to_string()), flag: false, lat: None };
+        assert_eq!(encode(&rec), "1\ta\\tb\\\\c\\nd\\re\tÉbreuil\tf\t\\N");
+    }
+}
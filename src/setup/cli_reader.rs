@@ -2,18 +2,30 @@
  *
  ***************************************************************************/
 
- use clap::{command, Arg, ArgMatches};
+ use clap::{command, Arg, ArgMatches, Command};
  use crate::err::AppError;
+ use super::stages::{self, Stage, STAGE_NAMES};
  use std::ffi::OsString;
  
  pub struct CliPars {
      pub flags: Flags, 
+     pub stages: Vec<Stage>,
+     pub nearest: Option<NearestPars>,
+ }
+
+ #[derive(Debug, Clone, Copy)]
+ pub struct NearestPars {
+     pub lat: f64,
+     pub lng: f64,
+     pub count: usize,
  }
  
  #[derive(Debug, Clone, Copy)]
  pub struct Flags {
      pub import_data: bool,
      pub include_nonlatin: bool,
+     pub transliterate: bool,
+     pub rollback: bool,
      pub test_run: bool,
  }
  
@@ -25,24 +37,53 @@
  
      let mut r_flag = parse_result.get_flag("r_flag");
      let n_flag = parse_result.get_flag("n_flag");
+     let t_flag = parse_result.get_flag("t_flag");
      let z_flag = parse_result.get_flag("z_flag");
+     let b_flag = parse_result.get_flag("b_flag");
      
 
-     if !r_flag {
-         r_flag = true;  // import is the default
+     // The nearest subcommand looks up cities rather than importing them.
+
+     let nearest = parse_result.subcommand_matches("nearest").map(|m| NearestPars {
+         lat: *m.get_one::<f64>("lat").unwrap(),    // required args, so always present
+         lng: *m.get_one::<f64>("lng").unwrap(),
+         count: *m.get_one::<usize>("count").unwrap(),
+     });
+
+     if !r_flag && !b_flag && nearest.is_none() {
+         r_flag = true;  // import is the default, unless rolling back or looking up
      }
  
      let flags = Flags {
          import_data: r_flag,
          include_nonlatin: n_flag,
+         transliterate: t_flag,
+         rollback: b_flag,
          test_run: z_flag,
      };
+
+     // Stage options restrict the pipeline to a subset of its stages.
+     // Stage names have already been checked by clap.
+
+     let only = get_stage_list(&parse_result, "only");
+     let skip = get_stage_list(&parse_result, "skip");
+     let from = parse_result.get_one::<String>("from").and_then(|s| Stage::from_name(s));
  
      Ok(CliPars {
          flags: flags,
+         stages: stages::select_stages(&only, &skip, from),
+         nearest,
      })
  
  }
+
+
+ fn get_stage_list(parse_result: &ArgMatches, arg_name: &str) -> Vec<Stage> {
+     match parse_result.get_many::<String>(arg_name) {
+         Some(names) => names.filter_map(|s| Stage::from_name(s)).collect(),
+         None => Vec::new(),
+     }
+ }
  
  
  fn parse_args(args: Vec<OsString>) -> Result<ArgMatches, clap::Error> {
@@ -65,6 +106,23 @@
             .help("A flag signifying that non Latin names shopuld be included (are excluded by default)")
             .action(clap::ArgAction::SetTrue)
          )
+         .arg(
+             Arg::new("t_flag")
+            .short('t')
+            .long("transliterate")
+            .required(false)
+            .help("A flag signifying that non Latin names should also be added as transliterated Latin (ASCII) aliases")
+            .action(clap::ArgAction::SetTrue)
+         )
+        .arg(
+             Arg::new("b_flag")
+             .short('b')
+             .long("rollback")
+             .required(false)
+             .help("A flag signifying that the src schema should be swapped back to the previous version (src_prev)")
+             .action(clap::ArgAction::SetTrue)
+             .conflicts_with("r_flag")
+        )
         .arg(
              Arg::new("z_flag")
              .short('z')
@@ -73,6 +131,58 @@
              .help("A flag signifying that this is part of an integration test run - suppresses logs")
              .action(clap::ArgAction::SetTrue)
         )
+        .arg(
+             Arg::new("only")
+             .long("only")
+             .required(false)
+             .help("Run only the listed stages (comma separated)")
+             .value_delimiter(',')
+             .value_parser(STAGE_NAMES)
+             .conflicts_with_all(["skip", "from"])
+        )
+        .arg(
+             Arg::new("skip")
+             .long("skip")
+             .required(false)
+             .help("Run all stages except those listed (comma separated)")
+             .value_delimiter(',')
+             .value_parser(STAGE_NAMES)
+        )
+        .arg(
+             Arg::new("from")
+             .long("from")
+             .required(false)
+             .help("Run the pipeline starting at the named stage")
+             .value_parser(STAGE_NAMES)
+        )
+        .subcommand(
+             Command::new("nearest")
+             .about("Lists the cities nearest to a coordinate, using the data in src.cities")
+             .arg(
+                 Arg::new("lat")
+                 .long("lat")
+                 .required(true)
+                 .help("Latitude, in decimal degrees")
+                 .allow_negative_numbers(true)
+                 .value_parser(clap::value_parser!(f64))
+             )
+             .arg(
+                 Arg::new("lng")
+                 .long("lng")
+                 .required(true)
+                 .help("Longitude, in decimal degrees")
+                 .allow_negative_numbers(true)
+                 .value_parser(clap::value_parser!(f64))
+             )
+             .arg(
+                 Arg::new("count")
+                 .long("count")
+                 .required(false)
+                 .help("The number of cities to list (default 5)")
+                 .default_value("5")
+                 .value_parser(clap::value_parser!(usize))
+             )
+        )
      .try_get_matches_from(args)
  
  }
@@ -115,8 +225,21 @@
          let res = fetch_valid_arguments(test_args).unwrap();
          assert_eq!(res.flags.import_data, true);
          assert_eq!(res.flags.include_nonlatin, true);
+         assert!(!res.flags.transliterate);
          assert_eq!(res.flags.test_run, false);
      }
+
+     #[test]
+     fn check_cli_with_t_flag() {
+         let target = "dummy target";
+         let args : Vec<&str> = vec![target, "-t"];
+         let test_args = args.iter().map(|x| x.to_string().into()).collect::<Vec<OsString>>();
+ 
+         let res = fetch_valid_arguments(test_args).unwrap();
+         assert!(res.flags.import_data);
+         assert!(!res.flags.include_nonlatin);
+         assert!(res.flags.transliterate);
+     }
  
      #[test]
      fn check_cli_with_z_flags() {
@@ -142,6 +265,81 @@
          assert_eq!(res.flags.include_nonlatin, true);
          assert_eq!(res.flags.test_run, true);
      }
+
+     #[test]
+     fn check_cli_with_b_flag() {
+         let target = "dummy target";
+         let args : Vec<&str> = vec![target, "-b"];
+         let test_args = args.iter().map(|x| x.to_string().into()).collect::<Vec<OsString>>();
+
+         let res = fetch_valid_arguments(test_args).unwrap();
+         assert_eq!(res.flags.import_data, false);
+         assert_eq!(res.flags.rollback, true);
+     }
+
+     #[test]
+     fn check_cli_no_stage_options_runs_all() {
+         let target = "dummy target";
+         let args : Vec<&str> = vec![target];
+         let test_args = args.iter().map(|x| x.to_string().into()).collect::<Vec<OsString>>();
+
+         let res = fetch_valid_arguments(test_args).unwrap();
+         assert_eq!(res.stages, stages::ALL_STAGES.to_vec());
+     }
+
+     #[test]
+     fn check_cli_with_only_option() {
+         let target = "dummy target";
+         let args : Vec<&str> = vec![target, "--only", "scopes,cities"];
+         let test_args = args.iter().map(|x| x.to_string().into()).collect::<Vec<OsString>>();
+
+         let res = fetch_valid_arguments(test_args).unwrap();
+         assert_eq!(res.stages, vec![Stage::Cities, Stage::Scopes]);
+     }
+
+     #[test]
+     fn check_cli_with_skip_and_from_options() {
+         let target = "dummy target";
+         let args : Vec<&str> = vec![target, "--from", "alt_names", "--skip", "admins,scopes"];
+         let test_args = args.iter().map(|x| x.to_string().into()).collect::<Vec<OsString>>();
+
+         let res = fetch_valid_arguments(test_args).unwrap();
+         assert_eq!(res.stages, vec![Stage::AltNames, Stage::Countries, Stage::Cities]);
+     }
+
+     #[test]
+     fn check_cli_with_nearest_subcommand() {
+         let target = "dummy target";
+         let args : Vec<&str> = vec![target, "nearest", "--lat", "-33.87", "--lng", "151.21", "--count", "3"];
+         let test_args = args.iter().map(|x| x.to_string().into()).collect::<Vec<OsString>>();
+
+         let res = fetch_valid_arguments(test_args).unwrap();
+         assert_eq!(res.flags.import_data, false);
+         let nearest = res.nearest.unwrap();
+         assert_eq!(nearest.lat, -33.87);
+         assert_eq!(nearest.lng, 151.21);
+         assert_eq!(nearest.count, 3);
+     }
+
+     #[test]
+     #[should_panic]
+     fn check_cli_with_unknown_stage_panics() {
+         let target = "dummy target";
+         let args : Vec<&str> = vec![target, "--only", "regions"];
+         let test_args = args.iter().map(|x| x.to_string().into()).collect::<Vec<OsString>>();
+
+         let _res = fetch_valid_arguments(test_args).unwrap();
+     }
+
+     #[test]
+     #[should_panic]
+     fn check_cli_with_only_and_skip_panics() {
+         let target = "dummy target";
+         let args : Vec<&str> = vec![target, "--only", "cities", "--skip", "scopes"];
+         let test_args = args.iter().map(|x| x.to_string().into()).collect::<Vec<OsString>>();
+
+         let _res = fetch_valid_arguments(test_args).unwrap();
+     }
  
  }
  
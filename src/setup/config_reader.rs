@@ -3,15 +3,23 @@
  ***************************************************************************/
 
  use crate::AppError;
+ use super::stages::Stage;
  use std::sync::OnceLock;
  use toml;
  use serde::Deserialize;
- use std::path::PathBuf;
+ use std::path::{Path, PathBuf};
+ use std::collections::BTreeMap;
+ use unicode_script::Script;
+ use crate::normalise::{NameRules, NormalisationRules};
  
  #[derive(Debug, Deserialize)]
  pub struct TomlConfig {
      pub folders: Option<TomlFolderPars>, 
      pub database: Option<TomlDBPars>,
+     pub files: Option<TomlFilePars>,
+     pub cities: Option<TomlCityPars>,
+     pub names: Option<TomlNamePars>,
+     pub normalisation: Option<TomlNormalisationPars>,
  }
  
  #[derive(Debug, Deserialize)]
@@ -20,6 +28,75 @@
      pub log_folder_path: Option<String>,
  }
  
+ // Unknown keys are rejected so that a misspelt file setting 
+ // is not silently replaced by the default.
+
+ #[derive(Debug, Deserialize, Default)]
+ #[serde(deny_unknown_fields)]
+ pub struct TomlFilePars {
+     pub lang_codes: Option<String>,
+     pub alt_names: Option<String>,
+     pub admin1_codes: Option<String>,
+     pub admin2_codes: Option<String>,
+     pub countries: Option<String>,
+     pub cities: Option<String>,
+     pub no_country: Option<String>,
+ }
+
+ #[derive(Debug, Deserialize, Default)]
+ #[serde(deny_unknown_fields)]
+ pub struct TomlCityPars {
+     pub source: Option<CitySource>,
+     pub min_population: Option<i64>,
+     pub feature_codes: Option<Vec<String>>,
+     pub disamb_levels: Option<BTreeMap<String, u8>>,
+ }
+
+ #[derive(Debug, Deserialize, Default)]
+ #[serde(deny_unknown_fields)]
+ pub struct TomlNamePars {
+     pub scripts: Option<Vec<String>>,
+     pub writer_tasks: Option<usize>,
+ }
+
+ #[derive(Debug, Deserialize, Default)]
+ #[serde(deny_unknown_fields)]
+ pub struct TomlNormalisationPars {
+     pub trim: Option<bool>,
+     pub replacements: Option<Vec<(String, String)>>,
+ }
+
+ // The Geonames files from which cities can be taken. The citiesN files
+ // hold populated places with a population of at least N (plus admin seats), 
+ // allCountries holds every feature, so is normally used with a population floor.
+
+ #[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+ pub enum CitySource {
+     #[serde(rename = "cities500")]
+     Cities500,
+     #[serde(rename = "cities1000")]
+     Cities1000,
+     #[serde(rename = "cities5000")]
+     #[default]
+     Cities5000,
+     #[serde(rename = "cities15000")]
+     Cities15000,
+     #[serde(rename = "allCountries")]
+     AllCountries,
+ }
+
+ impl CitySource {
+     pub fn name(&self) -> &'static str {
+         match self {
+             CitySource::Cities500 => "cities500",
+             CitySource::Cities1000 => "cities1000",
+             CitySource::Cities5000 => "cities5000",
+             CitySource::Cities15000 => "cities15000",
+             CitySource::AllCountries => "allCountries",
+         }
+     }
+ }
+
  #[derive(Debug, Deserialize)]
  pub struct TomlDBPars {
      pub db_host: Option<String>,
@@ -31,13 +108,90 @@
  
  pub struct Config {
      pub folders: FolderPars, 
+     pub files: FilePars,
+     pub cities: CityPars,
+     pub names: NamePars,
+     pub normalisation: NormalisationRules,
      pub db_pars: DBPars,
  }
+
+ // Only populated places (feature class P) are imported as cities. If no feature
+ // codes are listed, all P codes are accepted, except that those in
+ // EXCLUDED_CITY_CODES are dropped from allCountries.
+
+ // disamb_levels gives the admin level (1 or 2) used to distinguish same-named
+ // cities in each listed country, the configured levels merged over the defaults. 
+ // Unlisted countries use admin1.
+
+ #[derive(Debug, Clone)]
+ pub struct CityPars {
+     pub source: CitySource,
+     pub min_population: i64,
+     pub feature_codes: Option<Vec<String>>,
+     pub disamb_levels: BTreeMap<String, u8>,
+ }
+
+ impl CityPars {
+     pub fn disamb_level(&self, country_code: &str) -> u8 {
+         self.disamb_levels.get(country_code).copied().unwrap_or(1)
+     }
+ }
+
+ // scripts lists the Unicode scripts in which alternate names are retained. 
+ // Names using any other script are dropped (unless the -n flag is used).
+
+ // writer_tasks is the number of tasks writing alternate names to the 
+ // database at the same time, each using its own connection.
+
+ #[derive(Debug, Clone)]
+ pub struct NamePars {
+     pub scripts: Vec<Script>,
+     pub writer_tasks: usize,
+ }
+
+ pub const DEFAULT_NAME_SCRIPTS: [Script; 1] = [Script::Latin];
+
+ pub const DEFAULT_WRITER_TASKS: usize = 4;
+ pub const MAX_WRITER_TASKS: usize = 16;
+
+ pub const EXCLUDED_CITY_CODES: [&str; 5] = ["PPLCH", "PPLH", "PPLQ", "PPLW", "PPLX"];
+
+ // Use admin2 unless configured otherwise - in these countries admin1 areas 
+ // are too large to distinguish cities with the same name.
+
+ pub const DEFAULT_ADMIN2_COUNTRIES: [&str; 2] = ["FR", "GB"];
  
  pub struct FolderPars {
      pub data_folder_path: PathBuf,
      pub log_folder_path: PathBuf,
  }
+
+ // Full paths of each source file. Relative names are within the data folder.
+
+ #[derive(Debug, Clone)]
+ pub struct FilePars {
+     pub lang_codes: PathBuf,
+     pub alt_names: PathBuf,
+     pub admin1_codes: PathBuf,
+     pub admin2_codes: PathBuf,
+     pub countries: PathBuf,
+     pub cities: PathBuf,
+     pub no_country: PathBuf,
+ }
+
+ impl FilePars {
+     pub fn for_stage(&self, stage: Stage) -> Vec<&PathBuf> {
+         match stage {
+             Stage::LangCodes => vec![&self.lang_codes],
+             Stage::AltNames => vec![&self.alt_names, &self.countries, &self.admin1_codes, 
+                                     &self.admin2_codes, &self.cities, &self.no_country],
+             Stage::Admins => vec![&self.admin1_codes, &self.admin2_codes],
+             Stage::Countries => vec![&self.countries],
+             Stage::Cities => vec![&self.cities],
+             Stage::Scopes => vec![&self.no_country],
+         }
+     }
+ }
  
  #[derive(Debug, Clone)]
  pub struct DBPars {
@@ -53,8 +207,8 @@
  pub fn populate_config_vars(config_string: &String) -> Result<Config, AppError> {
      
      let toml_config = toml::from_str::<TomlConfig>(&config_string)
-         .map_err(|_| {AppError::ConfigurationError("Unable to parse config file.".to_string(),
-         "File (app_config.toml) may be malformed.".to_string())})?;
+         .map_err(|e| {AppError::ConfigurationError("Unable to parse config file.".to_string(),
+         format!("File (app_config.toml) may be malformed: {}", e.message()))})?;
  
      let toml_database = match toml_config.database {
          Some(d) => d,
@@ -65,16 +219,35 @@
      let toml_folders = match toml_config.folders {
          Some(f) => f,
          None => {return Result::Err(AppError::ConfigurationError("Missing or misspelt configuration section.".to_string(),
-            "Cannot find a section called '[files]'.".to_string()))},
+            "Cannot find a section called '[folders]'.".to_string()))},
      };
+
+     // The files section is optional - the standard Geonames names are used by default.
+
+     let toml_files = toml_config.files.unwrap_or_default();
+     let toml_cities = toml_config.cities.unwrap_or_default();
+     let toml_names = toml_config.names.unwrap_or_default();
     
      let config_folders = verify_folder_parameters(toml_folders)?;
+     let config_cities = verify_city_parameters(toml_cities)?;
+     let config_names = verify_name_parameters(toml_names)?;
+     // Without a normalisation section each table keeps its own default rules.
+
+     let config_normalisation = match toml_config.normalisation {
+         Some(n) => NormalisationRules::all(verify_normalisation_parameters(n)?),
+         None => NormalisationRules::default(),
+     };
+     let config_files = verify_file_parameters(toml_files, &config_folders.data_folder_path, config_cities.source)?;
      let config_db_pars = verify_db_parameters(toml_database)?;
  
      let _ = DB_PARS.set(config_db_pars.clone());
  
      Ok(Config{
          folders: config_folders,
+         files: config_files,
+         cities: config_cities,
+         names: config_names,
+         normalisation: config_normalisation,
          db_pars: config_db_pars,
      })
  }
@@ -96,6 +269,151 @@
  }
  
  
+ fn verify_city_parameters(toml_cities: TomlCityPars) -> Result<CityPars, AppError> {
+
+     let min_population = toml_cities.min_population.unwrap_or(0);
+     if min_population < 0 {
+         return Result::Err(AppError::ConfigurationError("Invalid city parameter in configuration file.".to_string(),
+         format!("min_population ({}) cannot be negative.", min_population)))
+     }
+
+     let feature_codes = match toml_cities.feature_codes {
+         Some(codes) => {
+             let codes: Vec<String> = codes.iter().map(|c| c.trim().to_uppercase()).collect();
+             if codes.is_empty() || codes.iter().any(|c| !c.starts_with("PPL")) {
+                 return Result::Err(AppError::ConfigurationError("Invalid city parameter in configuration file.".to_string(),
+                 "feature_codes must list one or more populated place (PPL...) codes.".to_string()))
+             }
+             Some(codes)
+         },
+         None => None,
+     };
+
+     // Configured levels are merged over the defaults, so countries can be added
+     // (or a default country set back to 1) without restating the others.
+
+     let mut disamb_levels: BTreeMap<String, u8> = DEFAULT_ADMIN2_COUNTRIES.iter().map(|c| (c.to_string(), 2)).collect();
+     for (country, level) in toml_cities.disamb_levels.unwrap_or_default() {
+         let country = country.trim().to_uppercase();
+         if country.len() != 2 || !country.chars().all(|c| c.is_ascii_alphabetic()) || !(1..=2).contains(&level) {
+             return Result::Err(AppError::ConfigurationError("Invalid city parameter in configuration file.".to_string(),
+             format!("disamb_levels entry {} = {} must be a two letter country code set to 1 or 2.", country, level)))
+         }
+         disamb_levels.insert(country, level);
+     }
+
+     Ok(CityPars {
+         source: toml_cities.source.unwrap_or_default(),
+         min_population,
+         feature_codes,
+         disamb_levels,
+     })
+ }
+
+
+ fn verify_name_parameters(toml_names: TomlNamePars) -> Result<NamePars, AppError> {
+
+     // Scripts may be given by their full Unicode name (e.g. "Cyrillic") 
+     // or their four letter ISO 15924 code (e.g. "Cyrl").
+
+     let scripts = match toml_names.scripts {
+         Some(names) => {
+             let mut scripts = Vec::new();
+             for name in names.iter().map(|n| n.trim()) {
+                 match Script::from_full_name(name).or_else(|| Script::from_short_name(name)) {
+                     Some(s) if !matches!(s, Script::Common | Script::Inherited | Script::Unknown) => scripts.push(s),
+                     _ => return Result::Err(AppError::ConfigurationError("Invalid name parameter in configuration file.".to_string(),
+                          format!("'{}' in scripts is not a recognised Unicode script name (e.g. Latin, Cyrillic, Greek).", name))),
+                 }
+             }
+             if scripts.is_empty() {
+                 return Result::Err(AppError::ConfigurationError("Invalid name parameter in configuration file.".to_string(),
+                 "scripts must list at least one Unicode script.".to_string()))
+             }
+             scripts
+         },
+         None => DEFAULT_NAME_SCRIPTS.to_vec(),
+     };
+
+     let writer_tasks = toml_names.writer_tasks.unwrap_or(DEFAULT_WRITER_TASKS);
+     if !(1..=MAX_WRITER_TASKS).contains(&writer_tasks) {
+         return Result::Err(AppError::ConfigurationError("Invalid name parameter in configuration file.".to_string(),
+         format!("writer_tasks must be between 1 and {}.", MAX_WRITER_TASKS)))
+     }
+
+     Ok(NamePars {
+         scripts,
+         writer_tasks,
+     })
+ }
+
+
+ fn verify_normalisation_parameters(toml_norm: TomlNormalisationPars) -> Result<NameRules, AppError> {
+
+     // If present the configured replacements replace the defaults entirely,
+     // so an empty list leaves names as they are in the source files.
+
+     let defaults = NameRules::default();
+     let replacements = match toml_norm.replacements {
+         Some(reps) => {
+             if reps.iter().any(|(from, _)| from.is_empty()) {
+                 return Result::Err(AppError::ConfigurationError("Invalid normalisation parameter in configuration file.".to_string(),
+                 "Each replacement must be a pair of strings, the first not empty, e.g. [\".\", \"\"].".to_string()))
+             }
+             reps
+         },
+         None => defaults.replacements,
+     };
+
+     Ok(NameRules {
+         trim: toml_norm.trim.unwrap_or(defaults.trim),
+         replacements,
+     })
+ }
+
+
+ fn verify_file_parameters(toml_files: TomlFilePars, data_folder: &Path, city_source: CitySource) -> Result<FilePars, AppError> {
+
+     // Each file may be given as a name within the data folder or as a full path elsewhere.
+     // The default cities file depends on the chosen city source.
+
+     let cities_default = format!("{}.txt", city_source.name());
+
+     Ok(FilePars {
+         lang_codes: check_file_path(toml_files.lang_codes, "lang_codes", "iso-languagecodes.txt", data_folder)?,
+         alt_names: check_file_path(toml_files.alt_names, "alt_names", "alternateNamesV2.txt", data_folder)?,
+         admin1_codes: check_file_path(toml_files.admin1_codes, "admin1_codes", "admin1CodesASCII.txt", data_folder)?,
+         admin2_codes: check_file_path(toml_files.admin2_codes, "admin2_codes", "admin2Codes.txt", data_folder)?,
+         countries: check_file_path(toml_files.countries, "countries", "countryInfo.txt", data_folder)?,
+         cities: check_file_path(toml_files.cities, "cities", &cities_default, data_folder)?,
+         no_country: check_file_path(toml_files.no_country, "no_country", "no-country.txt", data_folder)?,
+     })
+ }
+
+
+ fn check_file_path (src_name: Option<String>, config_name: &str, default: &str, data_folder: &Path) -> Result<PathBuf, AppError> {
+
+     let file_string = match src_name {
+         Some(s) if s.trim() != "" => s.trim().to_string(),
+         _ => default.to_string(),
+     };
+
+     let file_path = PathBuf::from(&file_string);
+     let extension = file_path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+     if !["txt", "zip", "gz"].contains(&extension.as_str()) {
+         return Result::Err(AppError::ConfigurationError("Invalid source file name in configuration file.".to_string(),
+         format!("The value for {} ('{}') must be a .txt, .zip or .gz file.", config_name, file_string)))
+     }
+
+     if file_path.is_absolute() {
+         Ok(file_path)
+     }
+     else {
+         Ok(data_folder.join(file_path))
+     }
+ }
+ 
+ 
  fn verify_db_parameters(toml_database: TomlDBPars) -> Result<DBPars, AppError> {
  
      // Check user name and password first as there are no defaults for these values.
@@ -302,6 +620,358 @@
      }
  
  
+ #[test]
+     fn check_file_defaults_are_supplied() {
+
+         let config = r#"
+ [folders]
+ data_folder_path="/home/steve/Data/MDR source data/Geonames/data/Geonames 20251001"
+ 
+ [database]
+ db_user="user_name"
+ db_password="password"
+ "#;
+         let config_string = config.to_string();
+         let res = populate_config_vars(&config_string).unwrap();
+         let folder = PathBuf::from("/home/steve/Data/MDR source data/Geonames/data/Geonames 20251001");
+         assert_eq!(res.files.alt_names, folder.join("alternateNamesV2.txt"));
+         assert_eq!(res.files.cities, folder.join("cities5000.txt"));
+         assert_eq!(res.files.no_country, folder.join("no-country.txt"));
+         assert_eq!(res.files.for_stage(Stage::Admins), vec![&folder.join("admin1CodesASCII.txt"), &folder.join("admin2Codes.txt")]);
+     }
+
+
+     #[test]
+     fn check_file_overrides_are_used() {
+
+         let config = r#"
+ [folders]
+ data_folder_path="/home/steve/Data/MDR source data/Geonames/data/Geonames 20251001"
+
+ [files]
+ cities="cities15000.zip"
+ alt_names="/home/steve/Data/shared/alternateNamesV2.zip"
+ countries=""
+ 
+ [database]
+ db_user="user_name"
+ db_password="password"
+ "#;
+         let config_string = config.to_string();
+         let res = populate_config_vars(&config_string).unwrap();
+         let folder = PathBuf::from("/home/steve/Data/MDR source data/Geonames/data/Geonames 20251001");
+         assert_eq!(res.files.cities, folder.join("cities15000.zip"));
+         assert_eq!(res.files.alt_names, PathBuf::from("/home/steve/Data/shared/alternateNamesV2.zip"));
+         assert_eq!(res.files.countries, folder.join("countryInfo.txt"));
+     }
+
+
+     #[test]
+     #[should_panic]
+     fn check_misspelt_file_key_panics() {
+
+         let config = r#"
+ [folders]
+ data_folder_path="/home/steve/Data/MDR source data/Geonames/data/Geonames 20251001"
+
+ [files]
+ citys="cities15000.txt"
+ 
+ [database]
+ db_user="user_name"
+ db_password="password"
+ "#;
+         let config_string = config.to_string();
+         let _res = populate_config_vars(&config_string).unwrap();
+     }
+
+
+     #[test]
+     #[should_panic]
+     fn check_wrong_file_type_panics() {
+
+         let config = r#"
+ [folders]
+ data_folder_path="/home/steve/Data/MDR source data/Geonames/data/Geonames 20251001"
+
+ [files]
+ cities="cities15000.csv"
+ 
+ [database]
+ db_user="user_name"
+ db_password="password"
+ "#;
+         let config_string = config.to_string();
+         let _res = populate_config_vars(&config_string).unwrap();
+     }
+
+
+ #[test]
+     fn check_city_defaults_are_supplied() {
+
+         let config = r#"
+ [folders]
+ data_folder_path="/home/steve/Data/MDR source data/Geonames/data/Geonames 20251001"
+ 
+ [database]
+ db_user="user_name"
+ db_password="password"
+ "#;
+         let config_string = config.to_string();
+         let res = populate_config_vars(&config_string).unwrap();
+         assert_eq!(res.cities.source, CitySource::Cities5000);
+         assert_eq!(res.cities.min_population, 0);
+         assert!(res.cities.feature_codes.is_none());
+         assert_eq!(res.cities.disamb_level("GB"), 2);
+         assert_eq!(res.cities.disamb_level("FR"), 2);
+         assert_eq!(res.cities.disamb_level("DE"), 1);
+         assert_eq!(res.names.scripts, vec![Script::Latin]);
+         assert_eq!(res.normalisation, NormalisationRules::default());
+     }
+
+
+     #[test]
+     fn check_disamb_levels_override_defaults() {
+
+         let config = r#"
+ [folders]
+ data_folder_path="/home/steve/Data/MDR source data/Geonames/data/Geonames 20251001"
+
+ [cities.disamb_levels]
+ GB=2
+ ie=2
+ IT=2
+ FR=1
+ 
+ [database]
+ db_user="user_name"
+ db_password="password"
+ "#;
+         let config_string = config.to_string();
+         let res = populate_config_vars(&config_string).unwrap();
+         assert_eq!(res.cities.disamb_level("GB"), 2);
+         assert_eq!(res.cities.disamb_level("IE"), 2);
+         assert_eq!(res.cities.disamb_level("IT"), 2);
+         assert_eq!(res.cities.disamb_level("FR"), 1);
+         assert_eq!(res.cities.disamb_level("DE"), 1);
+     }
+
+
+     #[test]
+     fn check_disamb_levels_are_merged_with_defaults() {
+
+         let config = r#"
+ [folders]
+ data_folder_path="/home/steve/Data/MDR source data/Geonames/data/Geonames 20251001"
+
+ [cities.disamb_levels]
+ IE=2
+ 
+ [database]
+ db_user="user_name"
+ db_password="password"
+ "#;
+         let config_string = config.to_string();
+         let res = populate_config_vars(&config_string).unwrap();
+         assert_eq!(res.cities.disamb_level("IE"), 2);
+         assert_eq!(res.cities.disamb_level("GB"), 2);
+         assert_eq!(res.cities.disamb_level("FR"), 2);
+         assert_eq!(res.cities.disamb_level("DE"), 1);
+     }
+
+
+     #[test]
+     #[should_panic]
+     fn check_invalid_disamb_level_panics() {
+
+         let config = r#"
+ [folders]
+ data_folder_path="/home/steve/Data/MDR source data/Geonames/data/Geonames 20251001"
+
+ [cities.disamb_levels]
+ IE=3
+ 
+ [database]
+ db_user="user_name"
+ db_password="password"
+ "#;
+         let config_string = config.to_string();
+         let _res = populate_config_vars(&config_string).unwrap();
+     }
+
+
+     #[test]
+     fn check_city_source_sets_cities_file() {
+
+         let config = r#"
+ [folders]
+ data_folder_path="/home/steve/Data/MDR source data/Geonames/data/Geonames 20251001"
+
+ [cities]
+ source="allCountries"
+ min_population=1000
+ feature_codes=["PPL", "ppla", "PPLC"]
+ 
+ [database]
+ db_user="user_name"
+ db_password="password"
+ "#;
+         let config_string = config.to_string();
+         let res = populate_config_vars(&config_string).unwrap();
+         let folder = PathBuf::from("/home/steve/Data/MDR source data/Geonames/data/Geonames 20251001");
+         assert_eq!(res.cities.source, CitySource::AllCountries);
+         assert_eq!(res.cities.min_population, 1000);
+         assert_eq!(res.cities.feature_codes, Some(vec!["PPL".to_string(), "PPLA".to_string(), "PPLC".to_string()]));
+         assert_eq!(res.files.cities, folder.join("allCountries.txt"));
+     }
+
+
+     #[test]
+     fn check_name_scripts_read_correctly() {
+
+         let config = r#"
+ [folders]
+ data_folder_path="/home/steve/Data/MDR source data/Geonames/data/Geonames 20251001"
+
+ [names]
+ scripts=["Latin", "Cyrl", "Greek"]
+ 
+ [database]
+ db_user="user_name"
+ db_password="password"
+ "#;
+         let config_string = config.to_string();
+         let res = populate_config_vars(&config_string).unwrap();
+         assert_eq!(res.names.scripts, vec![Script::Latin, Script::Cyrillic, Script::Greek]);
+         assert_eq!(res.names.writer_tasks, DEFAULT_WRITER_TASKS);
+     }
+
+
+     #[test]
+     fn check_writer_tasks_read_correctly() {
+
+         let config = r#"
+ [folders]
+ data_folder_path="/home/steve/Data/MDR source data/Geonames/data/Geonames 20251001"
+
+ [names]
+ writer_tasks=8
+ 
+ [database]
+ db_user="user_name"
+ db_password="password"
+ "#;
+         let config_string = config.to_string();
+         let res = populate_config_vars(&config_string).unwrap();
+         assert_eq!(res.names.scripts, DEFAULT_NAME_SCRIPTS.to_vec());
+         assert_eq!(res.names.writer_tasks, 8);
+     }
+
+
+     #[test]
+     #[should_panic]
+     fn check_zero_writer_tasks_panics() {
+
+         let config = r#"
+ [folders]
+ data_folder_path="/home/steve/Data/MDR source data/Geonames/data/Geonames 20251001"
+
+ [names]
+ writer_tasks=0
+ 
+ [database]
+ db_user="user_name"
+ db_password="password"
+ "#;
+         let config_string = config.to_string();
+         let _res = populate_config_vars(&config_string).unwrap();
+     }
+
+
+     #[test]
+     fn check_normalisation_rules_read_correctly() {
+
+         let config = r#"
+ [folders]
+ data_folder_path="/home/steve/Data/MDR source data/Geonames/data/Geonames 20251001"
+
+ [normalisation]
+ replacements=[["'", "’"]]
+ 
+ [database]
+ db_user="user_name"
+ db_password="password"
+ "#;
+         let config_string = config.to_string();
+         let res = populate_config_vars(&config_string).unwrap();
+         assert!(res.normalisation.names.trim);
+         assert_eq!(res.normalisation.names.replacements, vec![("'".to_string(), "’".to_string())]);
+         assert_eq!(res.normalisation.names.normalise("St. Louis "), "St. Louis");
+         assert_eq!(res.normalisation.regions, res.normalisation.names);
+         assert_eq!(res.normalisation.cities, res.normalisation.names);
+     }
+
+
+     #[test]
+     #[should_panic]
+     fn check_empty_replacement_panics() {
+
+         let config = r#"
+ [folders]
+ data_folder_path="/home/steve/Data/MDR source data/Geonames/data/Geonames 20251001"
+
+ [normalisation]
+ replacements=[["", "-"]]
+ 
+ [database]
+ db_user="user_name"
+ db_password="password"
+ "#;
+         let config_string = config.to_string();
+         let _res = populate_config_vars(&config_string).unwrap();
+     }
+
+
+     #[test]
+     #[should_panic]
+     fn check_unknown_script_panics() {
+
+         let config = r#"
+ [folders]
+ data_folder_path="/home/steve/Data/MDR source data/Geonames/data/Geonames 20251001"
+
+ [names]
+ scripts=["Latin", "Klingon"]
+ 
+ [database]
+ db_user="user_name"
+ db_password="password"
+ "#;
+         let config_string = config.to_string();
+         let _res = populate_config_vars(&config_string).unwrap();
+     }
+
+
+     #[test]
+     #[should_panic]
+     fn check_unknown_city_source_panics() {
+
+         let config = r#"
+ [folders]
+ data_folder_path="/home/steve/Data/MDR source data/Geonames/data/Geonames 20251001"
+
+ [cities]
+ source="cities2000"
+ 
+ [database]
+ db_user="user_name"
+ db_password="password"
+ "#;
+         let config_string = config.to_string();
+         let _res = populate_config_vars(&config_string).unwrap();
+     }
+
+
  #[test]
      fn missing_port_gets_default() {
  
@@ -7,6 +7,7 @@
  use std::path::PathBuf;
  use crate::err::AppError;
  use crate::setup::InitParams;
+ use crate::setup::config_reader::CitySource;
  
  use log::{info, LevelFilter};
  use log4rs::{
@@ -73,8 +74,31 @@
      info!("");
      info!("data_folder: {}", ip.data_folder.display());
      info!("log_folder: {}", ip.log_folder.display());
+     info!("lang_codes file: {}", ip.files.lang_codes.display());
+     info!("alt_names file: {}", ip.files.alt_names.display());
+     info!("admin1_codes file: {}", ip.files.admin1_codes.display());
+     info!("admin2_codes file: {}", ip.files.admin2_codes.display());
+     info!("countries file: {}", ip.files.countries.display());
+     info!("cities file: {}", ip.files.cities.display());
+     info!("no_country file: {}", ip.files.no_country.display());
+     info!("city source: {}", ip.cities.source.name());
+     info!("city minimum population: {}", ip.cities.min_population);
+     info!("city feature codes: {}", match &ip.cities.feature_codes {
+                Some(codes) => codes.join(", "),
+                None if ip.cities.source == CitySource::AllCountries => 
+                        "all PPL codes except historical, abandoned, destroyed and sections".to_string(),
+                None => format!("all codes in {}", ip.cities.source.name()),
+            });
+     info!("countries disambiguated by admin2: {}", ip.cities.disamb_levels.iter()
+                .filter(|(_, level)| **level == 2).map(|(c, _)| c.as_str()).collect::<Vec<_>>().join(", "));
      info!("import_data: {}", ip.flags.import_data);
-     info!("include non Latin: {}", ip.flags.include_nonlatin);
+     info!("rollback: {}", ip.flags.rollback);
+     info!("include names in all scripts: {}", ip.flags.include_nonlatin);
+     info!("add transliterated aliases: {}", ip.flags.transliterate);
+     info!("name scripts retained: {}", ip.names.scripts.iter().map(|s| s.full_name()).collect::<Vec<_>>().join(", "));
+     info!("alternate name writer tasks: {}", ip.names.writer_tasks);
+     info!("name normalisation: {}", ip.normalisation.describe());
+     info!("stages: {}", ip.stages.iter().map(|s| s.name()).collect::<Vec<_>>().join(", "));
      info!("");
      info!("************************************");
      info!("");
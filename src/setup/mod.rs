@@ -1,21 +1,34 @@
 pub mod config_reader;
 pub mod log_helper;
 pub mod cli_reader;
+pub mod stages;
+pub mod staging;
+pub mod run_history;
+pub mod source_file;
+pub mod bulk_writer;
 
 use crate::err::AppError;
 use sqlx::postgres::{PgPoolOptions, PgConnectOptions, PgPool};
+use sqlx::{Pool, Postgres};
 use std::path::PathBuf;
 use cli_reader::{CliPars, Flags};
 use std::fs;
 use std::time::Duration;
 use sqlx::ConnectOptions;
-use config_reader::Config;
+use config_reader::{Config, FilePars, CityPars, NamePars};
 use std::sync::OnceLock;
+use stages::Stage;
+use crate::normalise::NormalisationRules;
 
 pub struct InitParams {
     pub data_folder: PathBuf,
     pub log_folder: PathBuf,
+    pub files: FilePars,
+    pub cities: CityPars,
+    pub names: NamePars,
+    pub normalisation: NormalisationRules,
     pub flags: Flags,
+    pub stages: Vec<Stage>,
 }
 
 pub static LOG_RUNNING: OnceLock<bool> = OnceLock::new();
@@ -30,6 +43,10 @@ pub fn get_params(cli_pars: CliPars, config_string: &String) -> Result<InitParam
     
     let config_file: Config = config_reader::populate_config_vars(&config_string)?; 
     let folder_pars = config_file.folders;  // guaranteed to exist
+    let files = config_file.files;          // defaults supplied if absent
+    let cities = config_file.cities;        // defaults supplied if absent
+    let names = config_file.names;          // defaults supplied if absent
+    let normalisation = config_file.normalisation;  // defaults supplied if absent
 
     let empty_pb = PathBuf::from("");
     let mut data_folder_good = true;
@@ -59,7 +76,12 @@ pub fn get_params(cli_pars: CliPars, config_string: &String) -> Result<InitParam
     Ok(InitParams {
         data_folder,
         log_folder,
+        files,
+        cities,
+        names,
+        normalisation,
         flags: cli_pars.flags,
+        stages: cli_pars.stages,
     })
 
 }
@@ -77,7 +99,7 @@ fn folder_exists(folder_name: &PathBuf) -> bool {
         
 
 
-pub async fn get_db_pool() -> Result<PgPool, AppError> {  
+pub async fn get_db_pool(max_connections: u32) -> Result<PgPool, AppError> {  
 
     // Establish DB name and thence the connection string
     // (done as two separate steps to allow for future development).
@@ -97,12 +119,29 @@ pub async fn get_db_pool() -> Result<PgPool, AppError> {
     opts = opts.log_slow_statements(log::LevelFilter::Warn, Duration::from_secs(3));
 
     PgPoolOptions::new()
-        .max_connections(5) 
+        .max_connections(max_connections) 
         .connect_with(opts).await
         .map_err(|e| AppError::DBPoolError(format!("Problem with connecting to database {} and obtaining Pool", db_name), e))
 }
 
 
+pub async fn create_schemas(pool: &Pool<Postgres>) -> Result<(), AppError> {
+
+    // Initial call to DB ensures schemas are in place whichever stages are
+    // run, and reduces spurious warnings.
+
+    let sql = r#"SET client_min_messages TO WARNING;  
+    create schema if not exists src;
+    create schema if not exists geo;
+    create schema if not exists mdr;"#;
+
+    sqlx::raw_sql(sql).execute(pool)
+        .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    Ok(())
+}
+
+
 pub fn establish_log(params: &InitParams) -> Result<(), AppError> {
 
     if !log_set_up() {  // can be called more than once in context of integration tests
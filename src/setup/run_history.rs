@@ -0,0 +1,283 @@
+/***************************************************************************
+ * Records each import run in the imp_geo schema - when it ran, with which
+ * parameters, which source files (identified by size, modification time
+ * and SHA-256) were used for each stage, and the row counts each stage
+ * produced - so that the Geonames snapshot behind the data can be traced.
+ ***************************************************************************/
+
+use crate::err::AppError;
+use super::InitParams;
+use super::stages::Stage;
+use super::source_file::resolve_source_path;
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::{Pool, Postgres};
+use log::warn;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+// Row counts are collected here by the stage modules as they log them,
+// and written to the run_stages table as each stage finishes.
+
+static STAGE_COUNTS: Mutex<Vec<(String, u64)>> = Mutex::new(Vec::new());
+
+// File hashes are kept for the run, as several stages may use the same file.
+
+static FILE_HASHES: Mutex<BTreeMap<PathBuf, String>> = Mutex::new(BTreeMap::new());
+
+
+pub fn record_count(label: &str, count: u64) {
+    if let Ok(mut counts) = STAGE_COUNTS.lock() {
+        counts.push((label.to_string(), count));
+    }
+}
+
+
+fn take_counts() -> serde_json::Value {
+    let counts = match STAGE_COUNTS.lock() {
+        Ok(mut c) => std::mem::take(&mut *c),
+        Err(_) => Vec::new(),
+    };
+    let map: serde_json::Map<String, serde_json::Value> = counts.into_iter()
+        .map(|(k, v)| (k, serde_json::Value::from(v)))
+        .collect();
+    serde_json::Value::Object(map)
+}
+
+
+pub async fn create_history_tables(pool: &Pool<Postgres>) -> Result<(), AppError> {
+
+    // Unlike the geo and src tables, these are never dropped.
+
+    let sql = r#"SET client_min_messages TO WARNING;
+        create schema if not exists imp_geo;
+
+        create table if not exists imp_geo.runs
+        (
+              id                int PRIMARY KEY GENERATED ALWAYS AS IDENTITY
+            , started_at        timestamptz
+            , ended_at          timestamptz
+            , status            varchar
+            , data_folder       varchar
+            , parameters        jsonb
+        );
+
+        create table if not exists imp_geo.run_stages
+        (
+              run_id            int references imp_geo.runs(id)
+            , stage             varchar
+            , started_at        timestamptz
+            , ended_at          timestamptz
+            , row_counts        jsonb
+            , primary key (run_id, stage)
+        );
+
+        alter table imp_geo.run_stages add column if not exists kept_ids_basis jsonb;
+
+        create table if not exists imp_geo.run_files
+        (
+              run_id            int references imp_geo.runs(id)
+            , stage             varchar
+            , file_path         varchar
+            , file_size         bigint
+            , modified_at       timestamptz
+            , sha256            varchar
+        );"#;
+
+    sqlx::raw_sql(sql).execute(pool)
+        .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    Ok(())
+}
+
+
+pub async fn start_run(params: &InitParams, pool: &Pool<Postgres>) -> Result<i32, AppError> {
+
+    let parameters = serde_json::json!({
+        "include_nonlatin": params.flags.include_nonlatin,
+        "transliterate": params.flags.transliterate,
+        "name_scripts": params.names.scripts.iter().map(|s| s.full_name()).collect::<Vec<_>>(),
+        "name_writer_tasks": params.names.writer_tasks,
+        "name_trim": params.normalisation.names.trim,
+        "name_replacements": params.normalisation.names.replacements,
+        "city_name_trim": params.normalisation.cities.trim,
+        "city_name_replacements": params.normalisation.cities.replacements,
+        "region_name_trim": params.normalisation.regions.trim,
+        "region_name_replacements": params.normalisation.regions.replacements,
+        "stages": params.stages.iter().map(|s| s.name()).collect::<Vec<_>>(),
+        "city_source": params.cities.source.name(),
+        "city_min_population": params.cities.min_population,
+        "city_feature_codes": params.cities.feature_codes,
+        "city_disamb_levels": params.cities.disamb_levels,
+    });
+
+    let sql = r#"insert into imp_geo.runs (started_at, status, data_folder, parameters)
+                 values (now(), 'running', $1, $2::jsonb)
+                 returning id"#;
+
+    sqlx::query_scalar(sql)
+        .bind(params.data_folder.display().to_string()).bind(parameters.to_string())
+        .fetch_one(pool).await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))
+}
+
+
+pub async fn end_run(run_id: i32, status: &str, pool: &Pool<Postgres>) -> Result<(), AppError> {
+
+    let sql = r#"update imp_geo.runs set ended_at = now(), status = $2
+                 where id = $1"#;
+
+    sqlx::query(sql).bind(run_id).bind(status)
+        .execute(pool).await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    Ok(())
+}
+
+
+pub async fn start_stage(run_id: i32, stage: Stage, pool: &Pool<Postgres>) -> Result<(), AppError> {
+
+    take_counts();  // discards anything left over from an earlier stage
+
+    let sql = r#"insert into imp_geo.run_stages (run_id, stage, started_at)
+                 values ($1, $2, now())"#;
+
+    sqlx::query(sql).bind(run_id).bind(stage.name())
+        .execute(pool).await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    Ok(())
+}
+
+
+pub async fn end_stage(run_id: i32, stage: Stage, pool: &Pool<Postgres>) -> Result<(), AppError> {
+
+    let sql = r#"update imp_geo.run_stages set ended_at = now(), row_counts = $3::jsonb
+                 where run_id = $1 and stage = $2"#;
+
+    sqlx::query(sql).bind(run_id).bind(stage.name()).bind(take_counts().to_string())
+        .execute(pool).await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    Ok(())
+}
+
+
+fn kept_ids_basis(params: &InitParams) -> Result<serde_json::Value, AppError> {
+
+    // The settings and the cities file (by its hash, as a new download usually
+    // replaces the old one) that determine which cities have their alternate names kept.
+
+    let cities_path = resolve_source_path(&params.files.cities)?;
+
+    Ok(serde_json::json!({
+        "city_source": params.cities.source.name(),
+        "city_min_population": params.cities.min_population,
+        "city_feature_codes": params.cities.feature_codes,
+        "cities_file": params.files.cities.display().to_string(),
+        "cities_file_sha256": get_file_sha256(&cities_path)?,
+    }))
+}
+
+
+pub async fn record_kept_ids_basis(run_id: i32, params: &InitParams, pool: &Pool<Postgres>) -> Result<(), AppError> {
+
+    let sql = r#"update imp_geo.run_stages set kept_ids_basis = $3::jsonb
+                 where run_id = $1 and stage = $2"#;
+
+    sqlx::query(sql).bind(run_id).bind(Stage::AltNames.name()).bind(kept_ids_basis(params)?.to_string())
+        .execute(pool).await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    Ok(())
+}
+
+
+pub async fn check_kept_ids_basis(params: &InitParams, pool: &Pool<Postgres>) -> Result<(), AppError> {
+
+    // geo.alt_names only holds names for the cities selected when it was built. If the
+    // cities are imported without rebuilding it, they must be selected in the same way,
+    // from the same file, or any cities not selected then would have no names.
+
+    if !params.stages.contains(&Stage::Cities) || params.stages.contains(&Stage::AltNames) {
+        return Ok(());
+    }
+
+    let sql = r#"select kept_ids_basis::text from imp_geo.run_stages
+                 where stage = $1 and ended_at is not null
+                 order by run_id desc limit 1"#;
+
+    let previous: Option<Option<String>> = sqlx::query_scalar(sql).bind(Stage::AltNames.name())
+        .fetch_optional(pool).await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    match previous.flatten() {
+        Some(p) => {
+            let previous_basis: serde_json::Value = serde_json::from_str(&p)?;
+            let current_basis = kept_ids_basis(params)?;
+            if previous_basis != current_basis {
+                return Err(AppError::AltNamesOutOfDate(previous_basis.to_string(), current_basis.to_string()));
+            }
+        },
+        None => warn!("Unable to check that geo.alt_names was built with the current [cities] settings and file - \
+                       if these have changed the alt_names stage must be rerun"),
+    }
+
+    Ok(())
+}
+
+
+pub async fn record_source_file(run_id: i32, stage: Stage, file_path: &PathBuf, pool: &Pool<Postgres>) -> Result<(), AppError> {
+
+    let metadata = std::fs::metadata(file_path)
+                   .map_err(|e| AppError::IoReadErrorWithPath(e, file_path.to_owned()))?;
+    let modified_at: Option<DateTime<Utc>> = metadata.modified().ok().map(DateTime::<Utc>::from);
+    let sha256 = get_file_sha256(file_path)?;
+
+    let sql = r#"insert into imp_geo.run_files (run_id, stage, file_path, file_size, modified_at, sha256)
+                 values ($1, $2, $3, $4, $5, $6)"#;
+
+    sqlx::query(sql).bind(run_id).bind(stage.name())
+        .bind(file_path.display().to_string()).bind(metadata.len() as i64)
+        .bind(modified_at).bind(sha256)
+        .execute(pool).await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    Ok(())
+}
+
+
+fn get_file_sha256(file_path: &PathBuf) -> Result<String, AppError> {
+
+    if let Some(sha256) = FILE_HASHES.lock().ok().and_then(|h| h.get(file_path).cloned()) {
+        return Ok(sha256);
+    }
+    let sha256 = compute_file_sha256(file_path)?;
+    if let Ok(mut hashes) = FILE_HASHES.lock() {
+        hashes.insert(file_path.to_owned(), sha256.clone());
+    }
+    Ok(sha256)
+}
+
+
+fn compute_file_sha256(file_path: &PathBuf) -> Result<String, AppError> {
+
+    let file = File::open(file_path)
+               .map_err(|e| AppError::IoReadErrorWithPath(e, file_path.to_owned()))?;
+    let mut reader = BufReader::new(file);
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 65536];
+
+    loop {
+        let n = reader.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
@@ -0,0 +1,323 @@
+/***************************************************************************
+ * Locates and opens Geonames source files. Several of the files are
+ * distributed as zip archives (e.g. cities5000.zip), and any of them may
+ * have been compressed with gzip. If the .txt file itself is not in the
+ * data folder, a .zip or .gz of the same name is looked for instead, and
+ * the text inside is streamed directly, without extracting it to disk.
+ * 
+ * The text is read through a SourceReader, which removes the '#' comment
+ * lines and any header row, so that files can be used as downloaded.
+ ***************************************************************************/
+
+use crate::err::AppError;
+use crate::setup::run_history::record_count;
+use csv::{Reader, ReaderBuilder};
+use flate2::read::MultiGzDecoder;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+use log::info;
+
+pub enum SourceFile {
+    Text(File),
+    Zip(ZipArchive<File>, String),
+    Gzip(File),
+}
+
+
+pub fn resolve_source_path(file_path: &Path) -> Result<PathBuf, AppError> {
+
+    // The file as named is preferred if present, then any compressed version.
+
+    let folder = file_path.parent().unwrap_or(Path::new(""));
+    let file_name = file_name_of(file_path);
+    let stem = get_stem(&file_name);
+
+    let candidates = [
+        file_path.to_path_buf(),
+        folder.join(format!("{}.zip", stem)),
+        folder.join(format!("{}.gz", file_name)),
+        folder.join(format!("{}.gz", stem)),
+    ];
+
+    match candidates.into_iter().find(|p| p.is_file()) {
+        Some(p) => Ok(p),
+        None => Err(AppError::IoReadErrorWithPath(
+                    std::io::Error::new(std::io::ErrorKind::NotFound, "source file (or zip / gz version) not found"),
+                    file_path.to_path_buf())),
+    }
+}
+
+
+pub fn file_name_of(file_path: &Path) -> String {
+    match file_path.file_name() {
+        Some(n) => n.to_string_lossy().to_string(),
+        None => file_path.display().to_string(),
+    }
+}
+
+
+fn get_stem(file_name: &str) -> &str {
+    let mut stem = file_name;
+    for suffix in [".gz", ".zip", ".txt"] {
+        stem = stem.strip_suffix(suffix).unwrap_or(stem);
+    }
+    stem
+}
+
+
+impl SourceFile {
+
+    pub fn open(source_path: &PathBuf, file_name: &str) -> Result<SourceFile, AppError> {
+
+        let file = File::open(source_path)
+                   .map_err(|e| AppError::IoReadErrorWithPath(e, source_path.to_owned()))?;
+
+        let extension = source_path.extension().and_then(|e| e.to_str()).unwrap_or("");
+
+        match extension.to_lowercase().as_str() {
+            "zip" => {
+                let archive = ZipArchive::new(file)?;
+                let inner_name = find_zip_entry(&archive, file_name)
+                    .ok_or_else(|| AppError::IoReadErrorWithPath(
+                        std::io::Error::new(std::io::ErrorKind::NotFound, "no matching .txt file within zip archive"),
+                        source_path.to_owned()))?;
+                info!("Reading {} directly from {}", inner_name, source_path.display());
+                Ok(SourceFile::Zip(archive, inner_name))
+            },
+            "gz" => {
+                info!("Reading {} directly from {}", file_name, source_path.display());
+                Ok(SourceFile::Gzip(file))
+            },
+            _ => Ok(SourceFile::Text(file)),
+        }
+    }
+
+
+    // Resolves the source path (see resolve_source_path) and opens the file.
+
+    pub fn open_source(file_path: &Path) -> Result<SourceFile, AppError> {
+        let source_path = resolve_source_path(file_path)?;
+        SourceFile::open(&source_path, &file_name_of(file_path))
+    }
+
+
+    // A tab delimited csv reader over the text, read through a SourceReader. 
+    // has_headers is true only if the csv reader is to use the header row 
+    // itself, as for iso-languagecodes.txt.
+
+    pub fn csv_reader(&mut self, header_name: Option<&'static str>, has_headers: bool) -> Result<Reader<SourceReader<'_>>, AppError> {
+        let src_reader = SourceReader::new(self.reader()?, header_name);
+        Ok(ReaderBuilder::new()
+            .has_headers(has_headers)
+            .delimiter(9)
+            .from_reader(src_reader))
+    }
+
+
+    pub fn reader(&mut self) -> Result<Box<dyn Read + '_>, AppError> {
+        match self {
+            SourceFile::Text(f) => Ok(Box::new(f)),
+            SourceFile::Zip(archive, inner_name) => Ok(Box::new(archive.by_name(inner_name)?)),
+            SourceFile::Gzip(f) => Ok(Box::new(MultiGzDecoder::new(f))),
+        }
+    }
+}
+
+
+pub struct SourceReader<'a> {
+    inner: BufReader<Box<dyn Read + 'a>>,
+    line: Vec<u8>,
+    pos: usize,
+    header_name: Option<&'static str>,
+    at_start: bool,
+    pub comment_lines: u64,
+    pub header_lines: u64,
+}
+
+
+impl<'a> SourceReader<'a> {
+
+    // header_name is the (case insensitive) name of the first column in an 
+    // optional header row. If the first non comment line starts with that name 
+    // it is skipped. None is used when no header row is expected, or 
+    // when (as for iso-languagecodes.txt) the header is always present and used.
+
+    pub fn new(reader: Box<dyn Read + 'a>, header_name: Option<&'static str>) -> Self {
+        SourceReader {
+            inner: BufReader::new(reader),
+            line: Vec::new(),
+            pos: 0,
+            header_name,
+            at_start: true,
+            comment_lines: 0,
+            header_lines: 0,
+        }
+    }
+
+    fn is_header(&self) -> bool {
+        let header_name = match self.header_name {
+            Some(h) => h,
+            None => return false,
+        };
+        let first_field = self.line.split(|b| *b == b'\t').next().unwrap_or(&[]);
+        String::from_utf8_lossy(first_field).trim().eq_ignore_ascii_case(header_name)
+    }
+
+    fn next_data_line(&mut self) -> std::io::Result<()> {
+        loop {
+            self.line.clear();
+            self.pos = 0;
+            if self.inner.read_until(b'\n', &mut self.line)? == 0 {
+                return Ok(());   // end of file
+            }
+            if self.line.starts_with(b"#") {
+                self.comment_lines += 1;
+                continue;
+            }
+            if self.at_start {
+                self.at_start = false;
+                if self.is_header() {
+                    self.header_lines += 1;
+                    continue;
+                }
+            }
+            return Ok(());
+        }
+    }
+
+    pub fn log_skipped_lines(&self, source_file_name: &str) {
+        let skipped = self.comment_lines + self.header_lines;
+        if skipped > 0 {
+            info!("{} lines skipped in {} ({} comment, {} header)", skipped, source_file_name, 
+                    self.comment_lines, self.header_lines);
+            record_count(&format!("{} lines skipped", source_file_name), skipped);
+        }
+    }
+}
+
+
+impl Read for SourceReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos >= self.line.len() {
+            self.next_data_line()?;
+        }
+        let n = std::cmp::min(buf.len(), self.line.len() - self.pos);
+        buf[..n].copy_from_slice(&self.line[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+
+fn find_zip_entry(archive: &ZipArchive<File>, file_name: &str) -> Option<String> {
+
+    // The entry with the expected name is used if present (e.g. alternateNamesV2.zip
+    // also contains iso-languagecodes.txt), otherwise the first .txt entry.
+
+    let expected = format!("{}.txt", get_stem(file_name));
+    let names: Vec<&str> = archive.file_names().collect();
+    if let Some(n) = names.iter().find(|n| n.rsplit('/').next() == Some(expected.as_str())) {
+        return Some(n.to_string());
+    }
+    names.into_iter().find(|n| n.ends_with(".txt")).map(|n| n.to_string())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn get_test_folder(name: &str) -> PathBuf {
+        let folder = std::env::temp_dir().join(format!("imp_geo_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        folder
+    }
+
+    fn read_all(path: &PathBuf, file_name: &str) -> String {
+        let mut source = SourceFile::open(path, file_name).unwrap();
+        let mut s = String::new();
+        source.reader().unwrap().read_to_string(&mut s).unwrap();
+        s
+    }
+
+    #[test]
+    fn check_zip_used_when_no_text_file() {
+        let folder = get_test_folder("zip");
+        let zip_file = File::create(folder.join("cities5000.zip")).unwrap();
+        let mut zw = zip::ZipWriter::new(zip_file);
+        zw.start_file("readme.txt", zip::write::SimpleFileOptions::default()).unwrap();
+        zw.write_all(b"not this one").unwrap();
+        zw.start_file("cities5000.txt", zip::write::SimpleFileOptions::default()).unwrap();
+        zw.write_all(b"2643743\tLondon\n").unwrap();
+        zw.finish().unwrap();
+
+        let path = resolve_source_path(&folder.join("cities5000.txt")).unwrap();
+        assert_eq!(path, folder.join("cities5000.zip"));
+        assert_eq!(read_all(&path, "cities5000.txt"), "2643743\tLondon\n");
+        assert_eq!(read_all(&path, "cities5000.zip"), "2643743\tLondon\n");
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn check_gz_used_when_no_text_file() {
+        let folder = get_test_folder("gz");
+        let gz_file = File::create(folder.join("no-country.txt.gz")).unwrap();
+        let mut gw = flate2::write::GzEncoder::new(gz_file, flate2::Compression::default());
+        gw.write_all(b"6255148\tEurope\n").unwrap();
+        gw.finish().unwrap();
+
+        let path = resolve_source_path(&folder.join("no-country.txt")).unwrap();
+        assert_eq!(path, folder.join("no-country.txt.gz"));
+        assert_eq!(read_all(&path, "no-country.txt"), "6255148\tEurope\n");
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn check_comments_and_header_skipped() {
+        let text = "# GeoNames comment\n#ISO\tISO3\ngeonameid\tname\n2643743\tLondon\n# late comment\n2988507\tParis\n";
+        let mut rdr = SourceReader::new(Box::new(text.as_bytes()), Some("geonameid"));
+        let mut s = String::new();
+        rdr.read_to_string(&mut s).unwrap();
+        assert_eq!(s, "2643743\tLondon\n2988507\tParis\n");
+        assert_eq!(rdr.comment_lines, 3);
+        assert_eq!(rdr.header_lines, 1);
+    }
+
+    #[test]
+    fn check_file_without_header_unchanged() {
+        let text = "2643743\tLondon\n2988507\tParis";
+        let mut rdr = SourceReader::new(Box::new(text.as_bytes()), Some("geonameid"));
+        let mut s = String::new();
+        rdr.read_to_string(&mut s).unwrap();
+        assert_eq!(s, text);
+        assert_eq!(rdr.comment_lines + rdr.header_lines, 0);
+    }
+
+    #[test]
+    fn check_csv_reader_skips_comments_and_header() {
+        let folder = get_test_folder("csv");
+        std::fs::write(folder.join("no-country.txt"), "# comment\ngeonameid\tname\n6255148\tEurope\n").unwrap();
+
+        let mut source = SourceFile::open_source(&folder.join("no-country.txt")).unwrap();
+        let mut rdr = source.csv_reader(Some("geonameid"), false).unwrap();
+        let rows: Vec<(i64, String)> = rdr.deserialize().map(|r| r.unwrap()).collect();
+        assert_eq!(rows, vec![(6255148, "Europe".to_string())]);
+        assert_eq!(rdr.get_ref().comment_lines + rdr.get_ref().header_lines, 2);
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn check_text_file_preferred() {
+        let folder = get_test_folder("txt");
+        std::fs::write(folder.join("countryInfo.txt"), "GB\n").unwrap();
+        std::fs::write(folder.join("countryInfo.zip"), "not a zip").unwrap();
+
+        let path = resolve_source_path(&folder.join("countryInfo.txt")).unwrap();
+        assert_eq!(path, folder.join("countryInfo.txt"));
+        assert!(resolve_source_path(&folder.join("admin2Codes.txt")).is_err());
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -0,0 +1,155 @@
+/***************************************************************************
+ * The stages of the import pipeline, in the order in which they are run,
+ * together with the tables each stage creates and the tables it needs
+ * to already exist before it can start.
+ ***************************************************************************/
+
+use crate::err::AppError;
+use sqlx::{Pool, Postgres};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    LangCodes,
+    AltNames,
+    Countries,
+    Admins,
+    Cities,
+    Scopes,
+}
+
+pub const ALL_STAGES: [Stage; 6] = [
+    Stage::LangCodes,
+    Stage::AltNames,
+    Stage::Countries,
+    Stage::Admins,
+    Stage::Cities,
+    Stage::Scopes,
+];
+
+pub const STAGE_NAMES: [&str; 6] = ["lang_codes", "alt_names", "countries", "admins", "cities", "scopes"];
+
+
+impl Stage {
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::LangCodes => "lang_codes",
+            Stage::AltNames => "alt_names",
+            Stage::Admins => "admins",
+            Stage::Countries => "countries",
+            Stage::Cities => "cities",
+            Stage::Scopes => "scopes",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Stage> {
+        ALL_STAGES.iter().find(|s| s.name() == name.trim()).copied()
+    }
+
+    // Tables (re)created by the stage.
+
+    pub fn creates(&self) -> &'static [&'static str] {
+        match self {
+            Stage::LangCodes => &["geo.lang_codes", "src.lang_codes"],
+            Stage::AltNames => &["geo.alt_names", "src.place_codes", "src.external_ids"],
+            Stage::Countries => &["geo.countries", "src.countries", "src.country_names"],
+            Stage::Admins => &["geo.adm1s", "geo.adm2s", "src.admin1s", "src.admin2s", "src.admin_names"],
+            Stage::Cities => &["geo.cities", "src.cities", "src.city_names"],
+            Stage::Scopes => &["src.regions"],
+        }
+    }
+
+    // Tables that must be present, either from a previous run or from
+    // an earlier stage in this run, before the stage can start.
+
+    pub fn requires(&self) -> &'static [&'static str] {
+        match self {
+            Stage::LangCodes => &[],
+            Stage::AltNames => &["src.lang_codes"],
+            Stage::Countries => &["geo.alt_names"],
+            Stage::Admins => &["geo.alt_names", "src.countries"],
+            Stage::Cities => &["geo.alt_names", "geo.adm1s", "geo.adm2s", "src.countries"],
+            Stage::Scopes => &[],
+        }
+    }
+}
+
+
+pub fn select_stages(only: &[Stage], skip: &[Stage], from: Option<Stage>) -> Vec<Stage> {
+
+    // Stages are always returned in pipeline order, whatever
+    // the order in which they were listed on the command line.
+
+    if !only.is_empty() {
+        return ALL_STAGES.iter().filter(|s| only.contains(s)).copied().collect();
+    }
+
+    let start = match from {
+        Some(f) => ALL_STAGES.iter().position(|s| *s == f).unwrap_or(0),
+        None => 0,
+    };
+
+    ALL_STAGES[start..].iter().filter(|s| !skip.contains(s)).copied().collect()
+}
+
+
+pub async fn check_prerequisites(stages: &[Stage], pool: &Pool<Postgres>) -> Result<(), AppError> {
+
+    // Each required table must either be created by an earlier selected
+    // stage or already exist in the database. All stages are checked
+    // before any are run, so that a run does not fail part way through.
+
+    let mut created: Vec<&str> = Vec::new();
+    for stage in stages {
+        for table in stage.requires() {
+            if !created.contains(table) && !table_exists(table, pool).await? {
+                return Err(AppError::MissingStageTable(stage.name().to_string(), table.to_string()));
+            }
+        }
+        created.extend_from_slice(stage.creates());
+    }
+
+    Ok(())
+}
+
+
+async fn table_exists(table: &str, pool: &Pool<Postgres>) -> Result<bool, AppError> {
+
+    let sql = r#"select to_regclass($1) is not null"#;
+
+    sqlx::query_scalar(sql).bind(table)
+        .fetch_one(pool).await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_all_stages_selected_by_default() {
+        let res = select_stages(&[], &[], None);
+        assert_eq!(res, ALL_STAGES.to_vec());
+    }
+
+    #[test]
+    fn check_only_keeps_pipeline_order() {
+        let res = select_stages(&[Stage::Scopes, Stage::Cities], &[], None);
+        assert_eq!(res, vec![Stage::Cities, Stage::Scopes]);
+    }
+
+    #[test]
+    fn check_from_and_skip_combined() {
+        let res = select_stages(&[], &[Stage::Admins], Some(Stage::Countries));
+        assert_eq!(res, vec![Stage::Countries, Stage::Cities, Stage::Scopes]);
+    }
+
+    #[test]
+    fn check_stage_names_round_trip() {
+        for name in STAGE_NAMES {
+            assert_eq!(Stage::from_name(name).unwrap().name(), name);
+        }
+        assert!(Stage::from_name("regions").is_none());
+    }
+}
@@ -0,0 +1,108 @@
+/***************************************************************************
+ * All src tables are built in a staging schema (src_next), so that client
+ * systems continue to see a complete src schema while an import is running,
+ * and a failed run leaves src untouched. Once every stage has succeeded the
+ * staging schema is swapped for src in a single transaction, with the
+ * previous version kept as src_prev to allow a roll back.
+ ***************************************************************************/
+
+use crate::err::AppError;
+use super::stages::Stage;
+use sqlx::{Pool, Postgres};
+use log::info;
+
+
+pub async fn prepare_staging_schema(stages: &[Stage], pool: &Pool<Postgres>) -> Result<(), AppError> {
+
+    let sql = r#"SET client_min_messages TO WARNING;
+    drop schema if exists src_next cascade;
+    create schema src_next;"#;
+
+    sqlx::raw_sql(sql).execute(pool)
+        .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    // Tables in src that will not be rebuilt by this run are copied into
+    // the staging schema, so that the swap leaves them in place.
+
+    let rebuilt: Vec<String> = stages.iter()
+        .flat_map(|s| s.creates().iter())
+        .filter_map(|t| t.strip_prefix("src."))
+        .map(|t| t.to_string())
+        .collect();
+
+    let sql = r#"select table_name::text from information_schema.tables
+                 where table_schema = 'src' and table_type = 'BASE TABLE'
+                 and not (table_name = any($1))
+                 order by table_name"#;
+
+    let tables: Vec<String> = sqlx::query_scalar(sql).bind(&rebuilt)
+        .fetch_all(pool).await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    for table in tables.iter() {
+
+        let sql = format!(r#"create table src_next.{0} (like src.{0} including all);
+                  insert into src_next.{0} overriding system value
+                  select * from src.{0};"#, table);
+
+        sqlx::raw_sql(&sql).execute(pool)
+            .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+    }
+
+    info!("Staging schema src_next prepared, {} unchanged src tables copied", tables.len());
+
+    Ok(())
+}
+
+
+pub async fn publish_staging_schema(pool: &Pool<Postgres>) -> Result<(), AppError> {
+
+    let sql = r#"SET client_min_messages TO WARNING;
+    drop schema if exists src_prev cascade;
+    alter schema src rename to src_prev;
+    alter schema src_next rename to src;"#;
+
+    swap_in_transaction(sql, pool).await?;
+    info!("Staging schema published as src, previous version retained as src_prev");
+
+    Ok(())
+}
+
+
+pub async fn roll_back_to_previous(pool: &Pool<Postgres>) -> Result<(), AppError> {
+
+    // The current src becomes src_prev, so that a second roll back
+    // restores the original position.
+
+    let sql = r#"select to_regnamespace('src_prev') is not null"#;
+
+    let prev_exists: bool = sqlx::query_scalar(sql)
+        .fetch_one(pool).await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    if !prev_exists {
+        return Err(AppError::MissingPreviousSchema());
+    }
+
+    let sql = r#"alter schema src rename to src_swap;
+    alter schema src_prev rename to src;
+    alter schema src_swap rename to src_prev;"#;
+
+    swap_in_transaction(sql, pool).await?;
+    info!("src schema rolled back to the previous version");
+
+    Ok(())
+}
+
+
+async fn swap_in_transaction(sql: &str, pool: &Pool<Postgres>) -> Result<(), AppError> {
+
+    let mut tx = pool.begin().await
+        .map_err(|e| AppError::SqlxError(e, "begin transaction".to_string()))?;
+
+    sqlx::raw_sql(sql).execute(&mut *tx)
+        .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    tx.commit().await
+        .map_err(|e| AppError::SqlxError(e, "commit transaction".to_string()))
+}